@@ -1,5 +1,6 @@
 use byteorder::{ByteOrder, LittleEndian};
 use gnuplot::*;
+use hnsw::metrics::Euclidean;
 use hnsw::*;
 use rand::distributions::Standard;
 use rand::{Rng, SeedableRng};
@@ -11,20 +12,6 @@ use std::io::Read;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-struct Euclidean;
-
-impl Metric<&[f32]> for Euclidean {
-    type Unit = u32;
-    fn distance(&self, a: &&[f32], b: &&[f32]) -> u32 {
-        a.iter()
-            .zip(b.iter())
-            .map(|(&a, &b)| (a - b).powi(2))
-            .sum::<f32>()
-            .sqrt()
-            .to_bits()
-    }
-}
-
 #[derive(Debug, StructOpt)]
 #[structopt(name = "recall", about = "Generates recall graphs for HNSW")]
 struct Opt {
@@ -160,20 +147,26 @@ fn process<const M: usize, const M0: usize>(opt: &Opt) -> (Vec<f64>, Vec<f64>) {
         "Computing the correct nearest neighbor distance for all {} queries...",
         opt.num_queries
     );
+    let mut flat = FlatIndex::new(Euclidean);
+    let mut searcher = Searcher::default();
+    for feature in &search_space {
+        flat.insert(*feature, &mut searcher);
+    }
     let correct_worst_distances: Vec<_> = query_strings
         .iter()
-        .cloned()
         .map(|feature| {
-            let mut v = vec![];
-            for distance in search_space.iter().map(|n| Euclidean.distance(n, &feature)) {
-                let pos = v.binary_search(&distance).unwrap_or_else(|e| e);
-                v.insert(pos, distance);
-                if v.len() > opt.k {
-                    v.resize_with(opt.k, || unreachable!());
-                }
-            }
+            let mut dest = vec![
+                Neighbor {
+                    index: !0,
+                    distance: OrderedF32::new(f32::INFINITY),
+                };
+                opt.k
+            ];
             // Get the worst distance
-            v.into_iter().take(opt.k).last().unwrap()
+            flat.nearest(feature, opt.k, &mut searcher, &mut dest)
+                .last()
+                .unwrap()
+                .distance
         })
         .collect();
     eprintln!("Done.");
@@ -198,7 +191,7 @@ fn process<const M: usize, const M0: usize>(opt: &Opt) -> (Vec<f64>, Vec<f64>) {
             let dest = vec![
                 Neighbor {
                     index: !0,
-                    distance: !0,
+                    distance: OrderedF32::new(f32::INFINITY),
                 };
                 opt.k
             ];
@@ -145,20 +145,26 @@ where
         "Computing the correct nearest neighbor distance for all {} queries...",
         opt.num_queries
     );
+    let mut flat = FlatIndex::new(Hamming);
+    let mut searcher = Searcher::default();
+    for feature in &search_space {
+        flat.insert(feature.clone(), &mut searcher);
+    }
     let correct_worst_distances: Vec<_> = query_strings
         .iter()
-        .cloned()
         .map(|feature| {
-            let mut v = vec![];
-            for distance in search_space.iter().map(|n| Hamming.distance(n, &feature)) {
-                let pos = v.binary_search(&distance).unwrap_or_else(|e| e);
-                v.insert(pos, distance);
-                if v.len() > opt.k {
-                    v.resize_with(opt.k, || unreachable!());
-                }
-            }
+            let mut dest = vec![
+                Neighbor {
+                    index: !0,
+                    distance: Zero::zero(),
+                };
+                opt.k
+            ];
             // Get the worst distance
-            v.into_iter().take(opt.k).last().unwrap()
+            flat.nearest(feature, opt.k, &mut searcher, &mut dest)
+                .last()
+                .unwrap()
+                .distance
         })
         .collect();
     eprintln!("Done.");
@@ -0,0 +1,237 @@
+//! Measures the recall of an index against the true nearest neighbors.
+//!
+//! This lets a test suite check that a HNSW built from its own data finds enough of the true nearest neighbors,
+//! and choose the `ef` which gives the recall it needs. The true nearest neighbors can come with a dataset or be
+//! found with [`ground_truth`].
+
+use crate::{FlatIndex, Hnsw, NeighborSelector, Searcher};
+use num_traits::Zero;
+use rand_core::RngCore;
+use space::{Metric, Neighbor};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// An index which can be searched like [`Hnsw::nearest`], so it can be measured.
+pub trait Searchable<T> {
+    type Unit: Zero + Ord + Copy;
+
+    /// Searches for the nearest neighbors of `q` like [`Hnsw::nearest`].
+    fn search<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Self::Unit>,
+        dest: &'a mut [Neighbor<Self::Unit>],
+    ) -> &'a mut [Neighbor<Self::Unit>];
+
+    /// The number of distances computed by the last search with `searcher`.
+    fn distances(&self, searcher: &Searcher<Self::Unit>) -> usize;
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Searchable<T> for Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Unit = Met::Unit;
+
+    fn search<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        self.nearest(q, ef, searcher, dest)
+    }
+
+    fn distances(&self, searcher: &Searcher<Met::Unit>) -> usize {
+        searcher.distances()
+    }
+}
+
+#[cfg(feature = "frozen")]
+impl<'b, Met, T, const M: usize, const M0: usize> Searchable<T>
+    for crate::FrozenHnsw<'b, Met, T, M, M0>
+where
+    Met: Metric<T>,
+    T: bytemuck::Pod,
+{
+    type Unit = Met::Unit;
+
+    fn search<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        self.nearest(q, ef, searcher, dest)
+    }
+
+    fn distances(&self, searcher: &Searcher<Met::Unit>) -> usize {
+        searcher.distances()
+    }
+}
+
+impl<Met, T> Searchable<T> for FlatIndex<Met, T>
+where
+    Met: Metric<T>,
+{
+    type Unit = Met::Unit;
+
+    fn search<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        self.nearest(q, ef, searcher, dest)
+    }
+
+    /// Every search compares the query to every item.
+    fn distances(&self, _searcher: &Searcher<Met::Unit>) -> usize {
+        self.len()
+    }
+}
+
+/// The recall and cost of searching an index with one `ef`, returned by [`ef_sweep`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Measurement {
+    /// The `ef` used for every search.
+    pub ef: usize,
+    /// The fraction of the true `k` nearest neighbors that were found, like [`recall_at_k`].
+    pub recall: f64,
+    /// The mean time taken by a search.
+    pub latency: Duration,
+    /// The mean number of distances computed by a search.
+    pub distances: f64,
+}
+
+/// Finds the `k` nearest neighbors of every query exactly, to measure recall against.
+pub fn ground_truth<Met, T>(
+    index: &FlatIndex<Met, T>,
+    queries: &[T],
+    k: usize,
+) -> Vec<Vec<Neighbor<Met::Unit>>>
+where
+    Met: Metric<T>,
+{
+    let mut searcher = Searcher::default();
+    let mut dest = Vec::new();
+    queries
+        .iter()
+        .map(|q| {
+            dest.clear();
+            dest.resize(
+                k,
+                Neighbor {
+                    index: !0,
+                    distance: Zero::zero(),
+                },
+            );
+            index.nearest(q, k, &mut searcher, &mut dest).to_vec()
+        })
+        .collect()
+}
+
+/// Searches for the `k` nearest neighbors of every query with `ef`, and gives the fraction of the true `k`
+/// nearest neighbors that were found.
+///
+/// The `ground_truth` has the true nearest neighbors of every query, nearest first, like from
+/// [`ground_truth`]. A neighbor is counted as found if it is at most as far as the `k`th true nearest neighbor,
+/// so that any of the neighbors tied with it can be found instead. If the ground truth of a query has fewer
+/// than `k` neighbors, only that many are expected.
+///
+/// Panics if there isn't a ground truth for every query, or if `k` is `0`.
+pub fn recall_at_k<T, I>(
+    index: &I,
+    queries: &[T],
+    ground_truth: &[Vec<Neighbor<I::Unit>>],
+    k: usize,
+    ef: usize,
+) -> f64
+where
+    I: Searchable<T>,
+{
+    measure(index, queries, ground_truth, k, ef).recall
+}
+
+/// Measures the recall, latency and number of distances computed with every `ef`, like [`recall_at_k`].
+///
+/// This shows how much each `ef` costs, to pick the smallest one which gives the recall needed. The latency
+/// depends on the machine and anything else running on it, so it shouldn't be relied on in tests.
+pub fn ef_sweep<T, I>(
+    index: &I,
+    queries: &[T],
+    ground_truth: &[Vec<Neighbor<I::Unit>>],
+    k: usize,
+    efs: impl IntoIterator<Item = usize>,
+) -> Vec<Measurement>
+where
+    I: Searchable<T>,
+{
+    efs.into_iter()
+        .map(|ef| measure(index, queries, ground_truth, k, ef))
+        .collect()
+}
+
+fn measure<T, I>(
+    index: &I,
+    queries: &[T],
+    ground_truth: &[Vec<Neighbor<I::Unit>>],
+    k: usize,
+    ef: usize,
+) -> Measurement
+where
+    I: Searchable<T>,
+{
+    assert_eq!(
+        queries.len(),
+        ground_truth.len(),
+        "there must be a ground truth for every query"
+    );
+    assert!(k != 0, "k must be at least 1");
+    let mut searcher = Searcher::default();
+    let mut dest = Vec::new();
+    let mut found = 0;
+    let mut expected = 0;
+    let mut latency = Duration::ZERO;
+    let mut distances = 0;
+    for (q, truth) in queries.iter().zip(ground_truth) {
+        let expect = k.min(truth.len());
+        dest.clear();
+        dest.resize(
+            k,
+            Neighbor {
+                index: !0,
+                distance: Zero::zero(),
+            },
+        );
+        let start = Instant::now();
+        let neighbors = index.search(q, ef, &mut searcher, &mut dest);
+        latency += start.elapsed();
+        distances += index.distances(&searcher);
+        if let Some(worst) = truth[..expect].last() {
+            found += neighbors
+                .iter()
+                .take(expect)
+                .filter(|n| n.distance <= worst.distance)
+                .count();
+        }
+        expected += expect;
+    }
+    let searches = queries.len().max(1);
+    Measurement {
+        ef,
+        recall: if expected == 0 {
+            1.0
+        } else {
+            found as f64 / expected as f64
+        },
+        latency: latency / searches as u32,
+        distances: distances as f64 / searches as f64,
+    }
+}
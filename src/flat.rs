@@ -0,0 +1,113 @@
+use crate::Searcher;
+use alloc::{vec, vec::Vec};
+use num_traits::Zero;
+use space::{Knn, KnnPoints, Metric, Neighbor};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An exact nearest neighbor index which compares the query to every item.
+///
+/// It has the same `insert` and `nearest` methods as [`Hnsw`](crate::Hnsw), so it can take its place, such as
+/// for small collections or to find the true nearest neighbors when measuring recall. The `ef` and
+/// [`Searcher`] are ignored.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FlatIndex<Met, T> {
+    metric: Met,
+    features: Vec<T>,
+}
+
+impl<Met, T> FlatIndex<Met, T>
+where
+    Met: Metric<T>,
+{
+    /// Creates an empty index.
+    pub fn new(metric: Met) -> Self {
+        Self {
+            metric,
+            features: vec![],
+        }
+    }
+
+    /// Inserts a feature and returns its item, which is the number of items before it.
+    pub fn insert(&mut self, q: T, _searcher: &mut Searcher<Met::Unit>) -> usize {
+        self.features.push(q);
+        self.features.len() - 1
+    }
+
+    /// Finds the nearest items to `q`, as many as fit in `dest`, sorted from nearest to farthest.
+    ///
+    /// Items at the same distance are sorted by their item. Returns the part of `dest` which was filled.
+    pub fn nearest<'a>(
+        &self,
+        q: &T,
+        _ef: usize,
+        _searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        let mut len = 0;
+        for (index, feature) in self.features.iter().enumerate() {
+            let distance = self.metric.distance(q, feature);
+            let pos = dest[..len].partition_point(|n| n.distance <= distance);
+            if pos == dest.len() {
+                continue;
+            }
+            if len < dest.len() {
+                len += 1;
+            }
+            dest[pos..len].rotate_right(1);
+            dest[pos] = Neighbor { index, distance };
+        }
+        &mut dest[..len]
+    }
+
+    /// Extract the feature for a given item returned by [`FlatIndex::nearest`].
+    pub fn feature(&self, item: usize) -> &T {
+        &self.features[item]
+    }
+
+    /// Gets the metric given when the index was created.
+    pub fn metric(&self) -> &Met {
+        &self.metric
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+}
+
+impl<Met, T> Knn for FlatIndex<Met, T>
+where
+    Met: Metric<T>,
+{
+    type Ix = usize;
+    type Metric = Met;
+    type Point = T;
+    type KnnIter = Vec<Neighbor<Met::Unit>>;
+
+    fn knn(&self, query: &T, num: usize) -> Self::KnnIter {
+        let mut neighbors = vec![
+            Neighbor {
+                index: !0,
+                distance: Met::Unit::zero(),
+            };
+            num.min(self.len())
+        ];
+        self.nearest(query, num, &mut Searcher::default(), &mut neighbors);
+        neighbors
+    }
+}
+
+impl<Met, T> KnnPoints for FlatIndex<Met, T>
+where
+    Met: Metric<T>,
+{
+    fn get_point(&self, index: usize) -> &'_ T {
+        &self.features[index]
+    }
+}
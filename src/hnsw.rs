@@ -1,6 +1,12 @@
 mod hnsw_const;
+mod keyed;
+mod map;
 mod nodes;
+mod selection;
 #[cfg(feature = "serde")]
 mod serde_impl;
 
 pub use hnsw_const::*;
+pub use keyed::*;
+pub use map::*;
+pub use selection::*;
@@ -1,26 +1,54 @@
-use super::nodes::{HasNeighbors, Layer};
-use crate::hnsw::nodes::{NeighborNodes, Node};
+use crate::hnsw::nodes::{InboundNodes, NeighborNodes, Node};
 use crate::*;
 use alloc::{vec, vec::Vec};
+use core::{iter::Copied, slice::Iter};
 use num_traits::Zero;
 use rand_core::{RngCore, SeedableRng};
+use search::{Graph, GraphSearch};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use space::{Knn, KnnPoints, Metric, Neighbor};
 
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "frozen")]
+mod frozen;
+#[cfg(feature = "std")]
+mod locked;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "persist")]
+mod persist;
+mod reachable;
+mod search;
+mod stats;
+mod validate;
+
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentHnsw;
+#[cfg(feature = "frozen")]
+pub use frozen::{FrozenHnsw, FROZEN_MAGIC};
+#[cfg(feature = "persist")]
+pub use persist::{PersistError, FORMAT_VERSION, MAGIC};
+pub use stats::{HnswStats, LayerStats};
+pub use validate::ValidationError;
+
 /// This provides a HNSW implementation for any distance function.
 ///
 /// The type `T` must implement [`space::Metric`] to get implementations.
+///
+/// The type `S` decides which edges are kept in the graph. See [`NeighborSelector`].
 #[derive(Clone)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
     serde(bound(
-        serialize = "Met: Serialize, T: Serialize, R: Serialize",
-        deserialize = "Met: Deserialize<'de>, T: Deserialize<'de>, R: Deserialize<'de>"
-    ))
+        serialize = "Met: Serialize, T: Serialize, R: Serialize, S: Serialize",
+        deserialize = "Met: Deserialize<'de>, T: Deserialize<'de>, R: Deserialize<'de>, S: Deserialize<'de> + Default"
+    )),
+    serde(try_from = "validate::UncheckedHnsw<Met, T, R, M, M0, S>")
 )]
-pub struct Hnsw<Met, T, R, const M: usize, const M0: usize> {
+pub struct Hnsw<Met, T, R, const M: usize, const M0: usize, S = NeighborSelection> {
     /// Contains the space metric.
     metric: Met,
     /// Contains the zero layer.
@@ -35,6 +63,21 @@ pub struct Hnsw<Met, T, R, const M: usize, const M0: usize> {
     prng: R,
     /// The parameters for the HNSW.
     params: Params,
+    /// Marks the items which have been removed. This is only grown as far as the last removed item.
+    #[cfg_attr(feature = "serde", serde(default))]
+    removed: Vec<bool>,
+    /// The number of items marked as removed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    tombstones: usize,
+    /// Contains the inbound edges of the zero layer. This is empty unless inbound edges are enabled in the params.
+    #[cfg_attr(feature = "serde", serde(default))]
+    zero_inbound: Vec<InboundNodes<M0>>,
+    /// Contains the inbound edges of each non-zero layer. This is empty unless inbound edges are enabled in the params.
+    #[cfg_attr(feature = "serde", serde(default))]
+    layers_inbound: Vec<Vec<InboundNodes<M>>>,
+    /// Chooses the neighbors of each node.
+    #[cfg_attr(feature = "serde", serde(default))]
+    selector: S,
 }
 
 impl<Met, T, R, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0>
@@ -50,6 +93,11 @@ where
             layers: vec![],
             prng: R::from_seed(R::Seed::default()),
             params: Params::new(),
+            removed: vec![],
+            tombstones: 0,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector: Default::default(),
         }
     }
 
@@ -62,14 +110,20 @@ where
             layers: vec![],
             prng: R::from_seed(R::Seed::default()),
             params,
+            removed: vec![],
+            tombstones: 0,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector: params.neighbor_selection,
         }
     }
 }
 
-impl<Met, T, R, const M: usize, const M0: usize> Knn for Hnsw<Met, T, R, M, M0>
+impl<Met, T, R, S, const M: usize, const M0: usize> Knn for Hnsw<Met, T, R, M, M0, S>
 where
     R: RngCore,
     Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
 {
     type Ix = usize;
     type Metric = Met;
@@ -93,10 +147,11 @@ where
     }
 }
 
-impl<Met, T, R, const M: usize, const M0: usize> KnnPoints for Hnsw<Met, T, R, M, M0>
+impl<Met, T, R, S, const M: usize, const M0: usize> KnnPoints for Hnsw<Met, T, R, M, M0, S>
 where
     R: RngCore,
     Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
 {
     fn get_point(&self, index: usize) -> &'_ T {
         &self.features[index]
@@ -117,6 +172,11 @@ where
             layers: vec![],
             prng,
             params: Default::default(),
+            removed: vec![],
+            tombstones: 0,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector: Default::default(),
         }
     }
 
@@ -129,6 +189,40 @@ where
             layers: vec![],
             prng,
             params,
+            removed: vec![],
+            tombstones: 0,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector: params.neighbor_selection,
+        }
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Replaces the [`NeighborSelector`] which decides which edges are kept in the graph.
+    ///
+    /// Only edges created after this are affected, so this should usually be called before inserting anything.
+    pub fn with_selector<S2>(self, selector: S2) -> Hnsw<Met, T, R, M, M0, S2>
+    where
+        S2: NeighborSelector<Met, T>,
+    {
+        Hnsw {
+            metric: self.metric,
+            zero: self.zero,
+            features: self.features,
+            layers: self.layers,
+            prng: self.prng,
+            params: self.params,
+            removed: self.removed,
+            tombstones: self.tombstones,
+            zero_inbound: self.zero_inbound,
+            layers_inbound: self.layers_inbound,
+            selector,
         }
     }
 
@@ -136,12 +230,11 @@ where
     pub fn insert(&mut self, q: T, searcher: &mut Searcher<Met::Unit>) -> usize {
         // Get the level of this feature.
         let level = self.random_level();
-        let mut cap = if level >= self.layers.len() {
-            self.params.ef_construction
-        } else {
-            1
-        };
+        self.insert_level(q, level, searcher)
+    }
 
+    /// Inserts a feature into the HNSW on every layer up to `level`.
+    fn insert_level(&mut self, q: T, level: usize, searcher: &mut Searcher<Met::Unit>) -> usize {
         // If this is empty, none of this will work, so just add it manually.
         if self.is_empty() {
             // Add the zero node unconditionally.
@@ -149,6 +242,9 @@ where
                 neighbors: [!0; M0],
             });
             self.features.push(q);
+            if self.params.inbound_edges {
+                self.zero_inbound.push(InboundNodes::default());
+            }
 
             // Add all the layers its in.
             while self.layers.len() < level {
@@ -159,18 +255,44 @@ where
                     neighbors: NeighborNodes { neighbors: [!0; M] },
                 };
                 self.layers.push(vec![node]);
+                if self.params.inbound_edges {
+                    self.layers_inbound.push(vec![InboundNodes::default()]);
+                }
             }
             return 0;
         }
 
-        self.initialize_searcher(&q, searcher);
+        // Add the feature first so that the new node can be referred to by its index while it is linked.
+        let item = self.zero.len();
+        self.features.push(q);
+        let nearest = self.search_insertion(&self.features[item], level, searcher);
+        self.link_insertion(&nearest, level)
+    }
+
+    /// Finds the nearest neighbors of a feature being inserted on every layer up to `level` that it will be
+    /// linked to, which are indexed by layer.
+    fn search_insertion(
+        &self,
+        q: &T,
+        level: usize,
+        searcher: &mut Searcher<Met::Unit>,
+    ) -> Vec<Vec<Neighbor<Met::Unit>>> {
+        let mut nearest = vec![vec![]; core::cmp::min(level, self.layers.len()) + 1];
+        let mut cap = if level >= self.layers.len() {
+            self.params.ef_construction
+        } else {
+            1
+        };
+
+        let search = self.graph_search();
+        search.initialize_searcher(q, self.layers.len(), searcher);
 
         // Find the entry point on the level it was created by searching normally until its level.
         for ix in (level..self.layers.len()).rev() {
             // Perform an ANN search on this layer like normal.
-            self.search_single_layer(&q, searcher, Layer::NonZero(&self.layers[ix]), cap);
+            search.search_single_layer(q, searcher, ix + 1, cap, |_| true);
             // Then lower the search only after we create the node.
-            self.lower_search(&self.layers[ix], searcher);
+            search.lower_search(ix + 1, searcher);
             cap = if ix == level {
                 self.params.ef_construction
             } else {
@@ -178,22 +300,29 @@ where
             };
         }
 
-        // Then start from its level and connect it to its nearest neighbors.
+        // Then start from its level and find its nearest neighbors on each layer.
         for ix in (0..core::cmp::min(level, self.layers.len())).rev() {
             // Perform an ANN search on this layer like normal.
-            self.search_single_layer(&q, searcher, Layer::NonZero(&self.layers[ix]), cap);
-            // Then use the results of that search on this layer to connect the nodes.
-            self.create_node(&q, &searcher.nearest, ix + 1);
-            // Then lower the search only after we create the node.
-            self.lower_search(&self.layers[ix], searcher);
+            search.search_single_layer(q, searcher, ix + 1, cap, |_| true);
+            nearest[ix + 1] = searcher.nearest.clone();
+            // Then lower the search.
+            search.lower_search(ix + 1, searcher);
             cap = self.params.ef_construction;
         }
 
-        // Also search and connect the node to the zero layer.
-        self.search_zero_layer(&q, searcher, cap);
-        self.create_node(&q, &searcher.nearest, 0);
-        // Add the feature to the zero layer.
-        self.features.push(q);
+        // Also search the zero layer.
+        search.search_single_layer(q, searcher, 0, cap, |_| true);
+        nearest[0] = searcher.nearest.clone();
+        nearest
+    }
+
+    /// Links the last feature that was added into the graph given the `nearest` neighbors found by
+    /// [`Hnsw::search_insertion`], and returns its index.
+    fn link_insertion(&mut self, nearest: &[Vec<Neighbor<Met::Unit>>], level: usize) -> usize {
+        // Connect it to its nearest neighbors from its level down to the zero layer.
+        for (layer, nearest) in nearest.iter().enumerate().rev() {
+            self.create_node(nearest, layer);
+        }
 
         // Add all level vectors needed to be able to add this level.
         let zero_node = self.zero.len() - 1;
@@ -204,10 +333,98 @@ where
                 neighbors: NeighborNodes { neighbors: [!0; M] },
             };
             self.layers.push(vec![node]);
+            if self.params.inbound_edges {
+                self.layers_inbound.push(vec![InboundNodes::default()]);
+            }
         }
         zero_node
     }
 
+    /// Replaces the feature of an item in the HNSW, keeping its index.
+    ///
+    /// The item is relinked on every layer it is in. Its neighbors are found again by searching for the new
+    /// feature, and every node that pointed to it chooses its closest neighbors again, which prunes the edges
+    /// to the item that no longer make sense. Finding the nodes that point to the item takes a scan of every
+    /// layer it is in unless [`Params::inbound_edges`] is enabled.
+    pub fn update(&mut self, item: usize, q: T, searcher: &mut Searcher<Met::Unit>) {
+        self.features[item] = q;
+        let q = &self.features[item];
+
+        // Find the node of the item on every layer it is in, starting with the zero layer.
+        let mut nodes = vec![item];
+        for layer in &self.layers {
+            match layer.iter().position(|node| node.zero_node == item) {
+                Some(node_ix) => nodes.push(node_ix),
+                None => break,
+            }
+        }
+        let level = nodes.len() - 1;
+
+        // Search for the new neighbors on every layer the item is in the same way as insertion does.
+        let mut found = vec![vec![]; nodes.len()];
+        let others = |nearest: &[Neighbor<Met::Unit>], node_ix: usize| {
+            nearest
+                .iter()
+                .filter(|n| n.index != node_ix)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let search = self.graph_search();
+        search.initialize_searcher(q, self.layers.len(), searcher);
+        for ix in (0..self.layers.len()).rev() {
+            let cap = if ix < level {
+                self.params.ef_construction
+            } else {
+                1
+            };
+            search.search_single_layer(q, searcher, ix + 1, cap, |_| true);
+            if ix < level {
+                found[ix + 1] = others(&searcher.nearest, nodes[ix + 1]);
+            }
+            search.lower_search(ix + 1, searcher);
+        }
+        search.search_single_layer(q, searcher, 0, self.params.ef_construction, |_| true);
+        found[0] = others(&searcher.nearest, item);
+
+        for (layer, (&node_ix, found)) in nodes.iter().zip(&found).enumerate() {
+            let old_neighbors: Vec<usize> = self.neighbors(layer, node_ix).collect();
+            let new_neighbors = self.select_neighbors(layer, node_ix, found);
+            self.set_neighbors(layer, node_ix, &new_neighbors);
+
+            // Every node that pointed to the item chooses its neighbors again using the item's new feature.
+            // Without inbound edges, every node on the layer has to be checked.
+            let pointing: Vec<usize> = if self.params.inbound_edges {
+                let mut pointing = self.layer_inbound(layer, node_ix).to_vec();
+                pointing.sort_unstable();
+                pointing
+            } else {
+                (0..self.layer_len(layer))
+                    .filter(|&n| self.neighbors(layer, n).any(|n| n == node_ix))
+                    .collect()
+            };
+            for target_ix in pointing {
+                let mut candidates: Vec<usize> = self
+                    .neighbors(layer, target_ix)
+                    .chain(old_neighbors.iter().copied())
+                    .chain(new_neighbors.iter().map(|n| n.index))
+                    .filter(|&n| n != target_ix)
+                    .collect();
+                candidates.sort_unstable();
+                candidates.dedup();
+                let candidates = self.sorted_candidates(layer, target_ix, candidates);
+                let selected = self.select_neighbors(layer, target_ix, &candidates);
+                self.set_neighbors(layer, target_ix, &selected);
+            }
+
+            // Link the new neighbors back to the item.
+            for neighbor in new_neighbors {
+                if !self.neighbors(layer, neighbor.index).any(|n| n == node_ix) {
+                    self.add_neighbor(node_ix, neighbor.index, neighbor.distance, layer);
+                }
+            }
+        }
+    }
+
     /// Does a k-NN search where `q` is the query element and it attempts to put up to `M` nearest neighbors into `dest`.
     /// `ef` is the candidate pool size. `ef` can be increased to get better recall at the expense of speed.
     /// If `ef` is less than `dest.len()` then `dest` will only be filled with `ef` elements.
@@ -223,6 +440,49 @@ where
         self.search_layer(q, ef, 0, searcher, dest)
     }
 
+    /// Performs the same search as [`Hnsw::nearest`], but only items for which `filter` returns `true` are
+    /// placed into `dest`.
+    ///
+    /// Items rejected by the filter are still traversed so that the graph stays navigable. A very selective
+    /// filter causes much more of the graph to be traversed, but does not require `ef` to be increased.
+    pub fn nearest_filtered<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        filter: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        self.search_layer_filtered(q, ef, 0, filter, searcher, dest)
+    }
+
+    /// Finds all items whose distance to `q` is less than or equal to `radius`.
+    ///
+    /// The search is repeated with a larger candidate pool until the pool is no longer filled entirely with items
+    /// inside the radius, so the number of results is not limited. Like [`Hnsw::nearest`], this is approximate.
+    ///
+    /// Returns the found neighbors sorted by distance. They are stored in the `searcher`.
+    pub fn within<'a>(
+        &self,
+        q: &T,
+        radius: Met::Unit,
+        searcher: &'a mut Searcher<Met::Unit>,
+    ) -> &'a [Neighbor<Met::Unit>] {
+        let mut cap = core::cmp::max(M0, 1);
+        loop {
+            self.search_searcher(q, cap, 0, |_| true, searcher);
+            // If every neighbor in the pool is within the radius, there may be more outside of the pool.
+            let full = searcher.nearest.len() == cap
+                && searcher.nearest.last().unwrap().distance <= radius;
+            if !full || cap >= self.len() {
+                break;
+            }
+            cap *= 2;
+        }
+        let found = searcher.nearest.partition_point(|n| n.distance <= radius);
+        &searcher.nearest[..found]
+    }
+
     /// Extract the feature for a given item returned by [`HNSW::nearest`].
     ///
     /// The `item` must be retrieved from [`HNSW::search_layer`].
@@ -240,10 +500,15 @@ where
         if level == 0 {
             item
         } else {
-            self.layers[level][item as usize].zero_node
+            self.layers[level - 1][item].zero_node
         }
     }
 
+    /// Gets the metric given when the HNSW was created.
+    pub fn metric(&self) -> &Met {
+        &self.metric
+    }
+
     pub fn layers(&self) -> usize {
         self.layers.len() + 1
     }
@@ -254,7 +519,7 @@ where
 
     pub fn layer_len(&self, level: usize) -> usize {
         if level == 0 {
-            self.features.len()
+            self.zero.len()
         } else if level < self.layers() {
             self.layers[level - 1].len()
         } else {
@@ -270,6 +535,162 @@ where
         self.layer_len(level) == 0
     }
 
+    /// Marks an item as removed, leaving a tombstone in its place.
+    ///
+    /// A removed item is never returned from [`Hnsw::nearest`] or [`Hnsw::search_layer`], but it stays in the
+    /// graph and searches still route through it. Its index remains reserved.
+    ///
+    /// Returns `true` if the item was not already removed.
+    pub fn remove(&mut self, item: usize) -> bool {
+        assert!(item < self.len(), "removed item {} is out of bounds", item);
+        if self.removed.len() <= item {
+            self.removed.resize(item + 1, false);
+        }
+        let newly_removed = !self.removed[item];
+        if newly_removed {
+            self.removed[item] = true;
+            self.tombstones += 1;
+        }
+        newly_removed
+    }
+
+    /// Checks if an item was removed with [`Hnsw::remove`].
+    pub fn is_removed(&self, item: usize) -> bool {
+        self.removed.get(item).copied().unwrap_or(false)
+    }
+
+    /// The number of items which have been removed, but still occupy space in the graph.
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Gets the neighbors of a node on a layer.
+    ///
+    /// On layer `0` these are item indices, otherwise they are the unique indices on that layer as returned by
+    /// [`Hnsw::search_layer`].
+    pub fn neighbors(&self, level: usize, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.layer_neighbors(level, node)
+            .iter()
+            .copied()
+            .take_while(|&n| n != !0)
+    }
+
+    /// Gets the nodes on a layer which have the given node as a neighbor.
+    ///
+    /// On layer `0` these are item indices, otherwise they are the unique indices on that layer as returned by
+    /// [`Hnsw::search_layer`]. Returns `None` unless inbound edges are enabled with [`Params::inbound_edges`].
+    pub fn inbound(&self, level: usize, node: usize) -> Option<&[usize]> {
+        if self.params.inbound_edges {
+            Some(self.layer_inbound(level, node))
+        } else {
+            None
+        }
+    }
+
+    /// Physically deletes all items removed with [`Hnsw::remove`] from the graph.
+    ///
+    /// Every remaining node that pointed to a removed node is reconnected to the closest remaining nodes
+    /// that were reachable within two hops through its removed neighbors. The remaining items are renumbered to be contiguous
+    /// while preserving their order.
+    ///
+    /// Returns a mapping from each old item index to its new item index, or `!0` if the item was deleted.
+    pub fn compact(&mut self) -> Vec<usize> {
+        if self.tombstones == 0 {
+            return (0..self.len()).collect();
+        }
+
+        // Reconnect the remaining nodes before anything is renumbered.
+        for layer in 0..self.layers() {
+            self.repair_removed_neighbors(layer);
+        }
+
+        // Compute the new index of every node in every layer.
+        let mut next_index = 0;
+        let zero_map: Vec<usize> = (0..self.len())
+            .map(|item| {
+                if self.is_removed(item) {
+                    !0
+                } else {
+                    next_index += 1;
+                    next_index - 1
+                }
+            })
+            .collect();
+        let layer_maps: Vec<Vec<usize>> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut next_index = 0;
+                layer
+                    .iter()
+                    .map(|node| {
+                        if self.is_removed(node.zero_node) {
+                            !0
+                        } else {
+                            next_index += 1;
+                            next_index - 1
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let remap = |map: &[usize], neighbors: &mut [usize]| {
+            for neighbor in neighbors.iter_mut().take_while(|n| **n != !0) {
+                *neighbor = map[*neighbor];
+            }
+        };
+
+        let removed = core::mem::take(&mut self.removed);
+        let is_removed = |item: usize| removed.get(item).copied().unwrap_or(false);
+        self.tombstones = 0;
+
+        // Rebuild the zero layer and the features.
+        self.zero = core::mem::take(&mut self.zero)
+            .into_iter()
+            .enumerate()
+            .filter(|&(item, _)| !is_removed(item))
+            .map(|(_, mut node)| {
+                remap(&zero_map, &mut node.neighbors);
+                node
+            })
+            .collect();
+        self.features = core::mem::take(&mut self.features)
+            .into_iter()
+            .enumerate()
+            .filter(|&(item, _)| !is_removed(item))
+            .map(|(_, feature)| feature)
+            .collect();
+
+        // Rebuild every non-zero layer.
+        for (ix, layer) in self.layers.iter_mut().enumerate() {
+            let next_map = if ix == 0 {
+                &zero_map
+            } else {
+                &layer_maps[ix - 1]
+            };
+            *layer = core::mem::take(layer)
+                .into_iter()
+                .filter(|node| !is_removed(node.zero_node))
+                .map(|mut node| {
+                    node.zero_node = zero_map[node.zero_node];
+                    node.next_node = next_map[node.next_node];
+                    remap(&layer_maps[ix], &mut node.neighbors.neighbors);
+                    node
+                })
+                .collect();
+        }
+        // The top layers may have been emptied entirely.
+        while self.layers.last().map(Vec::is_empty).unwrap_or(false) {
+            self.layers.pop();
+        }
+        if self.params.inbound_edges {
+            self.rebuild_inbound();
+        }
+
+        zero_map
+    }
+
     /// Performs the same algorithm as [`HNSW::nearest`], but stops on a particular layer of the network
     /// and returns the unique index on that layer rather than the item index.
     ///
@@ -283,165 +704,67 @@ where
         searcher: &mut Searcher<Met::Unit>,
         dest: &'a mut [Neighbor<Met::Unit>],
     ) -> &'a mut [Neighbor<Met::Unit>] {
-        // If there is nothing in here, then just return nothing.
-        if self.features.is_empty() || level >= self.layers() {
-            return &mut [];
-        }
-
-        self.initialize_searcher(q, searcher);
-        let cap = 1;
-
-        for (ix, layer) in self.layers.iter().enumerate().rev() {
-            self.search_single_layer(q, searcher, Layer::NonZero(layer), cap);
-            if ix + 1 == level {
-                let found = core::cmp::min(dest.len(), searcher.nearest.len());
-                dest.copy_from_slice(&searcher.nearest[..found]);
-                return &mut dest[..found];
-            }
-            self.lower_search(layer, searcher);
-        }
-
-        let cap = ef;
-
-        // search the zero layer
-        self.search_zero_layer(q, searcher, cap);
+        self.search_layer_filtered(q, ef, level, |_| true, searcher, dest)
+    }
 
+    /// Performs [`HNSW::search_layer`], but only places items for which `filter` returns `true` into `dest`.
+    fn search_layer_filtered<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        level: usize,
+        filter: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        self.search_searcher(q, ef, level, filter, searcher);
         let found = core::cmp::min(dest.len(), searcher.nearest.len());
-        dest.copy_from_slice(&searcher.nearest[..found]);
+        dest[..found].copy_from_slice(&searcher.nearest[..found]);
         &mut dest[..found]
     }
 
-    /// Greedily finds the approximate nearest neighbors to `q` in a non-zero layer.
-    /// This corresponds to Algorithm 2 in the paper.
-    fn search_single_layer(
+    /// Performs the search of [`HNSW::search_layer`], leaving the sorted results in `searcher.nearest`.
+    fn search_searcher(
         &self,
         q: &T,
+        ef: usize,
+        level: usize,
+        filter: impl Fn(usize) -> bool,
         searcher: &mut Searcher<Met::Unit>,
-        layer: Layer<&[Node<M>]>,
-        cap: usize,
     ) {
-        while let Some(Neighbor { index, .. }) = searcher.candidates.pop() {
-            for neighbor in match layer {
-                Layer::NonZero(layer) => layer[index as usize].get_neighbors(),
-                Layer::Zero => self.zero[index as usize].get_neighbors(),
-            } {
-                let node_to_visit = match layer {
-                    Layer::NonZero(layer) => layer[neighbor as usize].zero_node,
-                    Layer::Zero => neighbor,
-                };
-
-                // Don't visit previously visited things. We use the zero node to allow reusing the seen filter
-                // across all layers since zero nodes are consistent among all layers.
-                // TODO: Use Cuckoo Filter or Bloom Filter to speed this up/take less memory.
-                if searcher.seen.insert(node_to_visit) {
-                    // Compute the distance of this neighbor.
-                    let distance = self
-                        .metric
-                        .distance(q, &self.features[node_to_visit as usize]);
-                    // Attempt to insert into nearest queue.
-                    let pos = searcher.nearest.partition_point(|n| n.distance <= distance);
-                    if pos != cap {
-                        // It was successful. Now we need to know if its full.
-                        if searcher.nearest.len() == cap {
-                            // In this case remove the worst item.
-                            searcher.nearest.pop();
-                        }
-                        // Either way, add the new item.
-                        let candidate = Neighbor {
-                            index: neighbor as usize,
-                            distance,
-                        };
-                        searcher.nearest.insert(pos, candidate);
-                        searcher.candidates.push(candidate);
-                    }
-                }
-            }
-        }
-    }
-
-    /// Greedily finds the approximate nearest neighbors to `q` in the zero layer.
-    fn search_zero_layer(&self, q: &T, searcher: &mut Searcher<Met::Unit>, cap: usize) {
-        self.search_single_layer(q, searcher, Layer::Zero, cap);
-    }
-
-    /// Ready a search for the next level down.
-    ///
-    /// `m` is the maximum number of nearest neighbors to consider during the search.
-    fn lower_search(&self, layer: &[Node<M>], searcher: &mut Searcher<Met::Unit>) {
-        // Clear the candidates so we can fill them with the best nodes in the last layer.
-        searcher.candidates.clear();
-        // Only preserve the best candidate. The original paper's algorithm uses `1` every time.
-        // See Algorithm 5 line 5 of the paper. The paper makes no further comment on why `1` was chosen.
-        let &Neighbor { index, distance } = searcher.nearest.first().unwrap();
-        searcher.nearest.clear();
-        // Update the node to the next layer.
-        let new_index = layer[index].next_node as usize;
-        let candidate = Neighbor {
-            index: new_index,
-            distance,
-        };
-        // Insert the index of the nearest neighbor into the nearest pool for the next layer.
-        searcher.nearest.push(candidate);
-        // Insert the index into the candidate pool as well.
-        searcher.candidates.push(candidate);
-    }
-
-    /// Resets a searcher, but does not set the `cap` on the nearest neighbors.
-    /// Must be passed the query element `q`.
-    fn initialize_searcher(&self, q: &T, searcher: &mut Searcher<Met::Unit>) {
-        // Clear the searcher.
-        searcher.clear();
-        // Add the entry point.
-        let entry_distance = self.metric.distance(q, self.entry_feature());
-        let candidate = Neighbor {
-            index: 0,
-            distance: entry_distance,
-        };
-        searcher.candidates.push(candidate);
-        searcher.nearest.push(candidate);
-        searcher.seen.insert(
-            self.layers
-                .last()
-                .map(|layer| layer[0].zero_node)
-                .unwrap_or(0),
-        );
+        let accept = |item| !self.is_removed(item) && filter(item);
+        self.graph_search()
+            .search(q, ef, level, self.layers.len(), accept, searcher);
     }
 
-    /// Gets the entry point's feature.
-    fn entry_feature(&self) -> &T {
-        if let Some(last_layer) = self.layers.last() {
-            &self.features[last_layer[0].zero_node as usize]
-        } else {
-            &self.features[0]
+    /// Searches the layers of the HNSW with the features of its items.
+    fn graph_search(&self) -> GraphSearch<'_, Met, T, Self> {
+        GraphSearch {
+            metric: &self.metric,
+            features: &self.features,
+            graph: self,
         }
     }
 
     /// Generates a correctly distributed random level as per Algorithm 1 line 4 of the paper.
     fn random_level(&mut self) -> usize {
-        let uniform: f64 = self.prng.next_u64() as f64 / core::u64::MAX as f64;
-        (-libm::log(uniform) * libm::log(M as f64).recip()) as usize
+        random_level::<M>(&mut self.prng)
     }
 
     /// Creates a new node at a layer given its nearest neighbors in that layer.
     /// This contains Algorithm 3 from the paper, but also includes some additional logic.
-    fn create_node(&mut self, q: &T, nearest: &[Neighbor<Met::Unit>], layer: usize) {
+    ///
+    /// The feature of the new node must already be added.
+    fn create_node(&mut self, nearest: &[Neighbor<Met::Unit>], layer: usize) {
+        let new_index = self.layer_len(layer);
         if layer == 0 {
-            let new_index = self.zero.len();
-            let mut neighbors: [usize; M0] = [!0; M0];
-            for (d, s) in neighbors.iter_mut().zip(nearest.iter()) {
-                *d = s.index as usize;
-            }
-            let node = NeighborNodes { neighbors };
-            for neighbor in node.get_neighbors() {
-                self.add_neighbor(q, new_index as usize, neighbor, layer);
+            self.zero.push(NeighborNodes {
+                neighbors: [!0; M0],
+            });
+            if self.params.inbound_edges {
+                self.zero_inbound.push(InboundNodes::default());
             }
-            self.zero.push(node);
         } else {
-            let new_index = self.layers[layer - 1].len();
-            let mut neighbors: [usize; M] = [!0; M];
-            for (d, s) in neighbors.iter_mut().zip(nearest.iter()) {
-                *d = s.index;
-            }
             let node = Node {
                 zero_node: self.zero.len(),
                 next_node: if layer == 1 {
@@ -449,85 +772,316 @@ where
                 } else {
                     self.layers[layer - 2].len()
                 },
-                neighbors: NeighborNodes { neighbors },
+                neighbors: NeighborNodes { neighbors: [!0; M] },
             };
-            for neighbor in node.get_neighbors() {
-                self.add_neighbor(q, new_index, neighbor, layer);
-            }
             self.layers[layer - 1].push(node);
+            if self.params.inbound_edges {
+                self.layers_inbound[layer - 1].push(InboundNodes::default());
+            }
+        }
+        let neighbors = self.select_neighbors(layer, new_index, nearest);
+        self.set_neighbors(layer, new_index, &neighbors);
+        for neighbor in neighbors {
+            self.add_neighbor(new_index, neighbor.index, neighbor.distance, layer);
         }
     }
 
     /// Attempts to add a neighbor to a target node.
-    fn add_neighbor(&mut self, q: &T, node_ix: usize, target_ix: usize, layer: usize) {
-        // Get the feature for the target and get the neighbor slice for the target.
-        // This is different for the zero layer.
-        let (target_feature, target_neighbors) = if layer == 0 {
-            (
-                &self.features[target_ix],
-                &self.zero[target_ix].neighbors[..],
-            )
-        } else {
-            let target = &self.layers[layer - 1][target_ix];
-            (
-                &self.features[target.zero_node],
-                &target.neighbors.neighbors[..],
-            )
+    ///
+    /// The `distance` is the distance between the node and the target.
+    fn add_neighbor(
+        &mut self,
+        node_ix: usize,
+        target_ix: usize,
+        distance: Met::Unit,
+        layer: usize,
+    ) {
+        let max_neighbors = if layer == 0 { M0 } else { M };
+        let new_neighbor = Neighbor {
+            index: node_ix,
+            distance,
         };
+        // Get the neighbor slice for the target. This is different for the zero layer.
+        let target_neighbors = self.layer_neighbors(layer, target_ix);
 
-        // Check if there is a point where the target has empty neighbor slots and add it there in that case.
+        // Check if there is a point where the target has empty neighbor slots and add it there in that case,
+        // unless the selector wants to choose the neighbors of the target again.
         let empty_point = target_neighbors.partition_point(|&n| n != !0);
-        if empty_point != target_neighbors.len() {
+        if empty_point != target_neighbors.len()
+            && self.selector.append_neighbor(
+                &self.metric,
+                &HnswLayer { hnsw: self, layer },
+                target_ix,
+                new_neighbor,
+                max_neighbors,
+            )
+        {
             // In this case we did find the first spot where the target was empty within the slice.
             // Now we add the neighbor to this slot.
-            if layer == 0 {
-                self.zero[target_ix as usize].neighbors[empty_point] = node_ix;
-            } else {
-                self.layers[layer - 1][target_ix as usize]
-                    .neighbors
-                    .neighbors[empty_point] = node_ix;
-            }
+            self.set_neighbor(layer, target_ix, empty_point, node_ix);
         } else {
-            // Otherwise, we need to find the worst neighbor currently.
-            let (worst_ix, worst_distance) = target_neighbors
+            // Otherwise, let the selector choose the neighbors of the target again including the new node.
+            let target_feature = &self.features[self.layer_zero_node(layer, target_ix)];
+            let neighbors: Vec<_> = target_neighbors[..empty_point]
                 .iter()
-                .enumerate()
-                .filter_map(|(ix, &n)| {
-                    // Compute the distance to be higher than possible if the neighbor is not filled yet so its always filled.
-                    if n == !0 {
-                        None
-                    } else {
-                        // Compute the distance. The feature is looked up differently for the zero layer.
-                        let distance = self.metric.distance(
-                            target_feature,
-                            &self.features[if layer == 0 {
-                                n
-                            } else {
-                                self.layers[layer - 1][n].zero_node
-                            }],
-                        );
-                        Some((ix, distance))
-                    }
+                .map(|&n| Neighbor {
+                    index: n,
+                    distance: self.metric.distance(
+                        target_feature,
+                        &self.features[self.layer_zero_node(layer, n)],
+                    ),
                 })
-                // This was done instead of max_by_key because min_by_key takes the first equally bad element.
-                .min_by_key(|&(_, distance)| core::cmp::Reverse(distance))
-                .unwrap();
-
-            // If this is better than the worst, insert it in the worst's place.
-            // This is also different for the zero layer.
-            if self.metric.distance(q, target_feature) < worst_distance {
-                if layer == 0 {
-                    self.zero[target_ix as usize].neighbors[worst_ix] = node_ix;
-                } else {
-                    self.layers[layer - 1][target_ix as usize]
-                        .neighbors
-                        .neighbors[worst_ix] = node_ix;
+                .collect();
+            let selected = self.selector.replace_neighbors(
+                &self.metric,
+                &HnswLayer { hnsw: self, layer },
+                target_ix,
+                &neighbors,
+                new_neighbor,
+                max_neighbors,
+            );
+            self.set_neighbors(layer, target_ix, &selected);
+        }
+    }
+
+    /// Computes the distance of each candidate from a node on a layer and sorts them by that distance.
+    fn sorted_candidates(
+        &self,
+        layer: usize,
+        node_ix: usize,
+        candidates: Vec<usize>,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        let feature = &self.features[self.layer_zero_node(layer, node_ix)];
+        let mut candidates: Vec<Neighbor<Met::Unit>> = candidates
+            .into_iter()
+            .map(|index| Neighbor {
+                index,
+                distance: self
+                    .metric
+                    .distance(feature, &self.features[self.layer_zero_node(layer, index)]),
+            })
+            .collect();
+        candidates.sort_by_key(|n| n.distance);
+        candidates
+    }
+
+    /// Chooses which of the `candidates` will be the neighbors of a node on a layer using the selector.
+    /// The `candidates` must be sorted by their distance from the node.
+    fn select_neighbors(
+        &self,
+        layer: usize,
+        node_ix: usize,
+        candidates: &[Neighbor<Met::Unit>],
+    ) -> Vec<Neighbor<Met::Unit>> {
+        let max_neighbors = if layer == 0 { M0 } else { M };
+        self.selector.select_neighbors(
+            &self.metric,
+            &HnswLayer { hnsw: self, layer },
+            node_ix,
+            candidates,
+            max_neighbors,
+        )
+    }
+
+    /// Gets the zero layer node (the item) that a node on a layer refers to.
+    fn layer_zero_node(&self, layer: usize, node_ix: usize) -> usize {
+        if layer == 0 {
+            node_ix
+        } else {
+            self.layers[layer - 1][node_ix].zero_node
+        }
+    }
+
+    /// Gets the neighbor slots of a node on a layer, including the empty (`!0`) slots.
+    fn layer_neighbors(&self, layer: usize, node_ix: usize) -> &[usize] {
+        if layer == 0 {
+            &self.zero[node_ix].neighbors
+        } else {
+            &self.layers[layer - 1][node_ix].neighbors.neighbors
+        }
+    }
+
+    /// Gets the neighbor slots of a node on a layer mutably, including the empty (`!0`) slots.
+    fn layer_neighbors_mut(&mut self, layer: usize, node_ix: usize) -> &mut [usize] {
+        if layer == 0 {
+            &mut self.zero[node_ix].neighbors
+        } else {
+            &mut self.layers[layer - 1][node_ix].neighbors.neighbors
+        }
+    }
+
+    /// Gets the nodes on a layer pointing to a node. This is empty unless inbound edges are enabled.
+    fn layer_inbound(&self, layer: usize, node_ix: usize) -> &[usize] {
+        if !self.params.inbound_edges {
+            &[]
+        } else if layer == 0 {
+            &self.zero_inbound[node_ix].neighbors
+        } else {
+            &self.layers_inbound[layer - 1][node_ix].neighbors
+        }
+    }
+
+    /// Records that `from_ix` points to `node_ix` on a layer.
+    fn add_inbound(&mut self, layer: usize, node_ix: usize, from_ix: usize) {
+        if layer == 0 {
+            self.zero_inbound[node_ix].neighbors.push(from_ix);
+        } else {
+            self.layers_inbound[layer - 1][node_ix]
+                .neighbors
+                .push(from_ix);
+        }
+    }
+
+    /// Records that `from_ix` no longer points to `node_ix` on a layer.
+    fn remove_inbound(&mut self, layer: usize, node_ix: usize, from_ix: usize) {
+        let pos = self
+            .layer_inbound(layer, node_ix)
+            .iter()
+            .position(|&n| n == from_ix)
+            .unwrap();
+        if layer == 0 {
+            self.zero_inbound[node_ix].neighbors.swap_remove(pos);
+        } else {
+            self.layers_inbound[layer - 1][node_ix]
+                .neighbors
+                .swap_remove(pos);
+        }
+    }
+
+    /// Sets a neighbor slot of a node on a layer, keeping the inbound edges consistent.
+    fn set_neighbor(&mut self, layer: usize, node_ix: usize, slot: usize, neighbor: usize) {
+        let old_neighbor = core::mem::replace(
+            &mut self.layer_neighbors_mut(layer, node_ix)[slot],
+            neighbor,
+        );
+        if self.params.inbound_edges && old_neighbor != neighbor {
+            if old_neighbor != !0 {
+                self.remove_inbound(layer, old_neighbor, node_ix);
+            }
+            if neighbor != !0 {
+                self.add_inbound(layer, neighbor, node_ix);
+            }
+        }
+    }
+
+    /// Recomputes the inbound edges of every node from the neighbors of every node.
+    fn rebuild_inbound(&mut self) {
+        self.zero_inbound = vec![InboundNodes::default(); self.zero.len()];
+        self.layers_inbound = self
+            .layers
+            .iter()
+            .map(|layer| vec![InboundNodes::default(); layer.len()])
+            .collect();
+        for layer in 0..self.layers() {
+            for node_ix in 0..self.layer_len(layer) {
+                for slot in 0..self.layer_neighbors(layer, node_ix).len() {
+                    let neighbor = self.layer_neighbors(layer, node_ix)[slot];
+                    if neighbor == !0 {
+                        break;
+                    }
+                    self.add_inbound(layer, neighbor, node_ix);
+                }
+            }
+        }
+    }
+
+    /// Replaces the removed neighbors of every remaining node on a layer with the remaining nodes chosen from
+    /// those reachable through the removed neighbors. This is used before removed nodes are physically deleted.
+    ///
+    /// Only the remaining nodes at most [`REPAIR_HOPS`] removed nodes away are considered, like hnswlib and FAISS
+    /// do, since the removed nodes can form one large component when many items are removed.
+    fn repair_removed_neighbors(&mut self, layer: usize) {
+        let mut repairs = vec![];
+        let mut seen: HashSet<usize, RandomState> =
+            HashSet::with_hasher(RandomState::with_seeds(0, 0, 0, 0));
+        let mut to_visit = vec![];
+        let mut next_visit = vec![];
+
+        // With inbound edges, only the nodes pointing to removed nodes need to be checked.
+        let to_check: Vec<usize> = if self.params.inbound_edges {
+            let mut to_check: Vec<usize> = (0..self.layer_len(layer))
+                .filter(|&n| self.is_removed(self.layer_zero_node(layer, n)))
+                .flat_map(|n| self.layer_inbound(layer, n).iter().copied())
+                .collect();
+            to_check.sort_unstable();
+            to_check.dedup();
+            to_check
+        } else {
+            (0..self.layer_len(layer)).collect()
+        };
+
+        for node_ix in to_check {
+            let item = self.layer_zero_node(layer, node_ix);
+            if self.is_removed(item)
+                || !self
+                    .layer_neighbors(layer, node_ix)
+                    .iter()
+                    .take_while(|&&n| n != !0)
+                    .any(|&n| self.is_removed(self.layer_zero_node(layer, n)))
+            {
+                continue;
+            }
+
+            // Gather the remaining neighbors and the remaining nodes within a few hops through removed nodes.
+            let mut candidates = vec![];
+            seen.clear();
+            seen.insert(node_ix);
+            to_visit.push(node_ix);
+            for hop in 0..=REPAIR_HOPS {
+                for visit_ix in to_visit.drain(..) {
+                    for &neighbor in self
+                        .layer_neighbors(layer, visit_ix)
+                        .iter()
+                        .take_while(|&&n| n != !0)
+                    {
+                        if seen.insert(neighbor) {
+                            let neighbor_item = self.layer_zero_node(layer, neighbor);
+                            if !self.is_removed(neighbor_item) {
+                                candidates.push(Neighbor {
+                                    index: neighbor,
+                                    distance: self.metric.distance(
+                                        &self.features[item],
+                                        &self.features[neighbor_item],
+                                    ),
+                                });
+                            } else if hop != REPAIR_HOPS {
+                                next_visit.push(neighbor);
+                            }
+                        }
+                    }
                 }
+                core::mem::swap(&mut to_visit, &mut next_visit);
             }
+            to_visit.clear();
+
+            candidates.sort_by_key(|n| n.distance);
+            repairs.push((node_ix, self.select_neighbors(layer, node_ix, &candidates)));
+        }
+
+        for (node_ix, candidates) in repairs {
+            self.set_neighbors(layer, node_ix, &candidates);
+        }
+    }
+
+    /// Replaces all the neighbors of a node on a layer with the first of the `neighbors` that fit.
+    fn set_neighbors(&mut self, layer: usize, node_ix: usize, neighbors: &[Neighbor<Met::Unit>]) {
+        let slots = if layer == 0 { M0 } else { M };
+        for slot in 0..slots {
+            let neighbor = neighbors.get(slot).map(|n| n.index).unwrap_or(!0);
+            self.set_neighbor(layer, node_ix, slot, neighbor);
         }
     }
 }
 
+/// The number of removed nodes in a row that [`Hnsw::compact`] looks through for the new neighbors of a node.
+const REPAIR_HOPS: usize = 2;
+
+/// Generates a correctly distributed random level as per Algorithm 1 line 4 of the paper.
+fn random_level<const M: usize>(prng: &mut impl RngCore) -> usize {
+    let uniform: f64 = prng.next_u64() as f64 / core::u64::MAX as f64;
+    (-libm::log(uniform) * libm::log(M as f64).recip()) as usize
+}
+
 impl<Met, T, R, const M: usize, const M0: usize> Default for Hnsw<Met, T, R, M, M0>
 where
     R: RngCore + SeedableRng,
@@ -537,3 +1091,80 @@ where
         Self::new(Met::default())
     }
 }
+
+/// A single layer of a [`Hnsw`] given to its [`NeighborSelector`].
+struct HnswLayer<'a, Met, T, R, S, const M: usize, const M0: usize> {
+    hnsw: &'a Hnsw<Met, T, R, M, M0, S>,
+    layer: usize,
+}
+
+impl<'a, Met, T, R, S, const M: usize, const M0: usize> LayerView<T>
+    for HnswLayer<'a, Met, T, R, S, M, M0>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    fn feature(&self, node: usize) -> &T {
+        &self.hnsw.features[self.hnsw.layer_zero_node(self.layer, node)]
+    }
+
+    type Neighbors<'b>
+        = &'a [usize]
+    where
+        Self: 'b;
+
+    fn neighbors(&self, node: usize) -> &'a [usize] {
+        let neighbors = self.hnsw.layer_neighbors(self.layer, node);
+        let len = neighbors.partition_point(|&n| n != !0);
+        &neighbors[..len]
+    }
+
+    fn is_removed(&self, node: usize) -> bool {
+        self.hnsw
+            .is_removed(self.hnsw.layer_zero_node(self.layer, node))
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Graph for Hnsw<Met, T, R, M, M0, S> {
+    type Slots<'a>
+        = Copied<Iter<'a, usize>>
+    where
+        Self: 'a;
+
+    fn layers(&self) -> usize {
+        self.layers.len() + 1
+    }
+
+    fn layer_len(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.zero.len()
+        } else {
+            self.layers[layer - 1].len()
+        }
+    }
+
+    fn zero_node(&self, layer: usize, node: usize) -> usize {
+        if layer == 0 {
+            node
+        } else {
+            self.layers[layer - 1][node].zero_node
+        }
+    }
+
+    fn next_node(&self, layer: usize, node: usize) -> usize {
+        self.layers[layer - 1][node].next_node
+    }
+
+    fn slots(&self, layer: usize, node: usize) -> Self::Slots<'_> {
+        if layer == 0 {
+            self.zero[node].neighbors.iter().copied()
+        } else {
+            self.layers[layer - 1][node]
+                .neighbors
+                .neighbors
+                .iter()
+                .copied()
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use super::locked::{Linker, LockedGraph};
+use super::random_level;
+use super::search::Graph;
+use crate::*;
+use alloc::{vec, vec::Vec};
+use rand_core::RngCore;
+use space::{Metric, Neighbor};
+use std::sync::{Mutex, RwLock};
+
+/// A [`Hnsw`] that can be shared between threads, which allows it to be searched while items are inserted.
+///
+/// Every node has a lock on its neighbors, so a search never sees the neighbors of a node while they are being
+/// changed. Searches only wait on an insert while the nodes of the new item are added and when the entry point
+/// changes, and not while the item is linked into the graph. Inserts wait on each other, so only one item is
+/// linked at a time, which also means the graph is the same as if the items were inserted into a [`Hnsw`].
+///
+/// Convert a [`Hnsw`] into this with [`From`] and back with [`ConcurrentHnsw::into_inner`].
+pub struct ConcurrentHnsw<Met, T, R, const M: usize, const M0: usize, S = NeighborSelection> {
+    metric: Met,
+    params: Params,
+    selector: S,
+    /// Inserts hold this the whole time, so they happen one at a time.
+    prng: Mutex<R>,
+    shared: RwLock<Shared<T, M, M0>>,
+}
+
+/// The parts of a [`ConcurrentHnsw`] that change when an item is inserted or removed.
+struct Shared<T, const M: usize, const M0: usize> {
+    features: Vec<T>,
+    removed: Vec<bool>,
+    tombstones: usize,
+    /// The layer of the entry point, which is the first node on this layer. Layers above this have
+    /// been added for an item that isn't linked yet.
+    top: usize,
+    graph: LockedGraph<M, M0>,
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> ConcurrentHnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Inserts a feature into the HNSW while it may be searched by other threads.
+    pub fn insert(&self, q: T, searcher: &mut Searcher<Met::Unit>) -> usize {
+        let mut prng = self.prng.lock().unwrap();
+        let level = random_level::<M>(&mut *prng);
+
+        // Add the nodes of the item, which can't be found until they are linked.
+        let (item, node) = {
+            let mut shared = self.shared.write().unwrap();
+            shared.features.push(q);
+            let node = shared.graph.push(level);
+            let item = shared.features.len() - 1;
+            if item == 0 {
+                // The first item is the entry point and has nothing to link to.
+                shared.top = level;
+                return item;
+            }
+            (item, node)
+        };
+
+        let top = {
+            let shared = self.shared.read().unwrap();
+            self.linker(&shared)
+                .insert(item, level, node, shared.top, searcher);
+            shared.top
+        };
+
+        // Now that it is linked, it can become the entry point.
+        if level > top {
+            self.shared.write().unwrap().top = level;
+        }
+        item
+    }
+
+    /// Marks an item as removed like [`Hnsw::remove`].
+    ///
+    /// Use [`ConcurrentHnsw::into_inner`] and then [`Hnsw::compact`] to delete removed items.
+    pub fn remove(&self, item: usize) -> bool {
+        let mut shared = self.shared.write().unwrap();
+        assert!(item < shared.features.len(), "item out of bounds");
+        if shared.removed.len() <= item {
+            shared.removed.resize(item + 1, false);
+        }
+        if shared.removed[item] {
+            false
+        } else {
+            shared.removed[item] = true;
+            shared.tombstones += 1;
+            true
+        }
+    }
+
+    /// Searches for the nearest neighbors of `q` like [`Hnsw::nearest`].
+    pub fn nearest<'a>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> &'a mut [Neighbor<Met::Unit>] {
+        let shared = self.shared.read().unwrap();
+        self.linker(&shared).nearest(q, ef, shared.top, searcher);
+        let found = core::cmp::min(dest.len(), searcher.nearest.len());
+        dest[..found].copy_from_slice(&searcher.nearest[..found]);
+        &mut dest[..found]
+    }
+
+    /// Gets a copy of the feature of an item.
+    pub fn feature(&self, item: usize) -> T
+    where
+        T: Clone,
+    {
+        self.shared.read().unwrap().features[item].clone()
+    }
+
+    /// Gets the neighbors of a node on a layer like [`Hnsw::neighbors`]. They are copied while the node is
+    /// locked, so they are always the neighbors the node had at one point in time.
+    pub fn neighbors(&self, level: usize, node: usize) -> Vec<usize> {
+        self.shared
+            .read()
+            .unwrap()
+            .graph
+            .read_neighbors(level, node)
+            .to_vec()
+    }
+
+    pub fn layers(&self) -> usize {
+        self.shared.read().unwrap().graph.layers()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.read().unwrap().features.len()
+    }
+
+    pub fn layer_len(&self, level: usize) -> usize {
+        let shared = self.shared.read().unwrap();
+        if level < shared.graph.layers() {
+            shared.graph.layer_len(level)
+        } else {
+            0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the [`Hnsw`] back once it is no longer shared.
+    pub fn into_inner(self) -> Hnsw<Met, T, R, M, M0, S> {
+        let shared = self.shared.into_inner().unwrap();
+        let (zero, layers) = shared.graph.into_parts();
+        let mut hnsw = Hnsw {
+            metric: self.metric,
+            zero,
+            features: shared.features,
+            layers,
+            prng: self.prng.into_inner().unwrap(),
+            params: self.params,
+            removed: shared.removed,
+            tombstones: shared.tombstones,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector: self.selector,
+        };
+        if hnsw.params.inbound_edges {
+            hnsw.rebuild_inbound();
+        }
+        hnsw
+    }
+
+    fn linker<'a>(&'a self, shared: &'a Shared<T, M, M0>) -> Linker<'a, Met, T, S, M, M0> {
+        Linker {
+            metric: &self.metric,
+            selector: &self.selector,
+            features: &shared.features,
+            removed: &shared.removed,
+            ef_construction: self.params.ef_construction,
+            graph: &shared.graph,
+        }
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> From<Hnsw<Met, T, R, M, M0, S>>
+    for ConcurrentHnsw<Met, T, R, M, M0, S>
+{
+    fn from(hnsw: Hnsw<Met, T, R, M, M0, S>) -> Self {
+        Self {
+            metric: hnsw.metric,
+            params: hnsw.params,
+            selector: hnsw.selector,
+            prng: Mutex::new(hnsw.prng),
+            shared: RwLock::new(Shared {
+                features: hnsw.features,
+                removed: hnsw.removed,
+                tombstones: hnsw.tombstones,
+                top: hnsw.layers.len(),
+                graph: LockedGraph::new(hnsw.zero, hnsw.layers),
+            }),
+        }
+    }
+}
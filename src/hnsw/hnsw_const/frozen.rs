@@ -0,0 +1,383 @@
+use super::persist::write_u64;
+use super::search::{Graph, GraphSearch};
+use super::validate::validate_graph;
+use crate::*;
+use alloc::vec::Vec;
+use bytemuck::Pod;
+use core::convert::{TryFrom, TryInto};
+use core::iter::Map;
+use core::mem::size_of;
+use core::slice::Iter;
+use space::{Metric, Neighbor};
+use std::io::Write;
+
+/// The first bytes of every HNSW saved with [`Hnsw::save_frozen`].
+pub const FROZEN_MAGIC: [u8; 8] = *b"HNSWFRZN";
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    T: Pod,
+{
+    /// Writes the graph and features in a flat format that can be used in place by [`FrozenHnsw`].
+    ///
+    /// The graph is written as little-endian integers and every section is aligned to 8 bytes, so the data
+    /// can be memory-mapped. The features are copied as they are in memory, so they must be loaded on a
+    /// platform with the same byte order, and their alignment must be no more than 8 bytes. The metric, PRNG,
+    /// selector and [`Params`] are not saved, since a [`FrozenHnsw`] is never inserted into.
+    ///
+    /// This performs many small writes, so the writer should be buffered.
+    pub fn save_frozen(&self, mut writer: impl Write) -> Result<(), PersistError> {
+        let writer = &mut writer;
+        writer.write_all(&FROZEN_MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(size_of::<T>() as u32).to_le_bytes())?;
+        write_u64(writer, M as u64)?;
+        write_u64(writer, M0 as u64)?;
+        write_u64(writer, self.zero.len() as u64)?;
+        write_u64(writer, self.layers.len() as u64)?;
+        write_u64(writer, self.tombstones as u64)?;
+        for layer in &self.layers {
+            write_u64(writer, layer.len() as u64)?;
+        }
+
+        for node in &self.zero {
+            write_slots(writer, &node.neighbors)?;
+        }
+        for layer in &self.layers {
+            for node in layer {
+                write_u64(writer, node.zero_node as u64)?;
+                write_u64(writer, node.next_node as u64)?;
+                write_slots(writer, &node.neighbors.neighbors)?;
+            }
+        }
+        for item in 0..self.zero.len() {
+            writer.write_all(&[self.removed.get(item).copied().unwrap_or(false) as u8])?;
+        }
+        writer.write_all(&[0; 8][..padding(self.zero.len())])?;
+        writer.write_all(bytemuck::cast_slice(&self.features))?;
+        Ok(())
+    }
+}
+
+/// A read-only [`Hnsw`] that uses the graph and features in place from data written with
+/// [`Hnsw::save_frozen`], which is usually a memory-mapped file.
+///
+/// Nothing is copied to the heap except the location of each layer. Every node is checked when it is opened
+/// like [`Hnsw::validate`], which reads the whole graph once, but the features are only read when they are
+/// searched. Searches give exactly the same results as the [`Hnsw`] that was saved.
+///
+/// ```no_run
+/// # use hnsw::FrozenHnsw;
+/// # use space::Metric;
+/// # struct Hamming;
+/// # impl Metric<u64> for Hamming {
+/// #     type Unit = u32;
+/// #     fn distance(&self, a: &u64, b: &u64) -> u32 { (a ^ b).count_ones() }
+/// # }
+/// let file = std::fs::File::open("index.hnsw").unwrap();
+/// let map = unsafe { memmap2::Mmap::map(&file).unwrap() };
+/// let hnsw: FrozenHnsw<Hamming, u64, 12, 24> = FrozenHnsw::from_bytes(Hamming, &map).unwrap();
+/// ```
+pub struct FrozenHnsw<'a, Met, T, const M: usize, const M0: usize> {
+    metric: Met,
+    /// The `M0` neighbor slots of every item.
+    zero: &'a [u64],
+    features: &'a [T],
+    /// Each non-zero layer, which has the zero node, next node and `M` neighbor slots of every node.
+    layers: Vec<&'a [u64]>,
+    removed: &'a [u8],
+    tombstones: usize,
+}
+
+impl<'a, Met, T, const M: usize, const M0: usize> FrozenHnsw<'a, Met, T, M, M0>
+where
+    Met: Metric<T>,
+    T: Pod,
+{
+    /// Uses a HNSW written with [`Hnsw::save_frozen`] in place.
+    ///
+    /// The data must be aligned to 8 bytes, which memory-mapped files always are. This fails with a
+    /// [`PersistError`] if the data was saved with a different `M`, `M0` or feature size, in a different
+    /// version of the format, is too short or too long, or if the graph fails the checks of [`Hnsw::validate`].
+    pub fn from_bytes(metric: Met, bytes: &'a [u8]) -> Result<Self, PersistError> {
+        if bytes.as_ptr() as usize & 7 != 0 {
+            return Err(PersistError::Misaligned);
+        }
+        let mut sections = Sections { bytes, offset: 0 };
+        if sections.take(8)? != FROZEN_MAGIC {
+            return Err(PersistError::BadMagic);
+        }
+        let version = u32::from_le_bytes(sections.take(4)?.try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(PersistError::UnsupportedVersion(version));
+        }
+        let found = u32::from_le_bytes(sections.take(4)?.try_into().unwrap()) as usize;
+        if found != size_of::<T>() {
+            return Err(PersistError::MismatchedFeatureSize {
+                expected: size_of::<T>(),
+                found,
+            });
+        }
+        let found = sections.take_usize()?;
+        if found != M {
+            return Err(PersistError::MismatchedM { expected: M, found });
+        }
+        let found = sections.take_usize()?;
+        if found != M0 {
+            return Err(PersistError::MismatchedM0 {
+                expected: M0,
+                found,
+            });
+        }
+        let len = sections.take_usize()?;
+        let layer_count = sections.take_usize()?;
+        let tombstones = sections.take_usize()?;
+        let layer_lens = (0..layer_count)
+            .map(|_| sections.take_usize())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let zero = sections.take_slice(len.checked_mul(M0))?;
+        let layers = layer_lens
+            .into_iter()
+            .map(|layer_len| sections.take_slice(layer_len.checked_mul(M + 2)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let removed = sections.take(len)?;
+        sections.take(padding(len))?;
+        let features = sections.take_slice(Some(len))?;
+        if sections.offset != bytes.len() {
+            return Err(PersistError::Corrupt("data continues after the features"));
+        }
+
+        let hnsw = Self {
+            metric,
+            zero,
+            features,
+            layers,
+            removed,
+            tombstones,
+        };
+        let removed = removed.iter().filter(|&&removed| removed != 0).count();
+        if tombstones != removed {
+            return Err(ValidationError::TombstoneCount {
+                tombstones,
+                removed,
+            }
+            .into());
+        }
+        validate_graph(&hnsw)?;
+        Ok(hnsw)
+    }
+
+    /// Searches for the nearest neighbors of `q` like [`Hnsw::nearest`].
+    pub fn nearest<'b>(
+        &self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'b mut [Neighbor<Met::Unit>],
+    ) -> &'b mut [Neighbor<Met::Unit>] {
+        self.search_layer(q, ef, 0, searcher, dest)
+    }
+
+    /// Searches for the nearest neighbors of `q` which pass the `filter` like [`Hnsw::nearest_filtered`].
+    pub fn nearest_filtered<'b>(
+        &self,
+        q: &T,
+        ef: usize,
+        filter: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'b mut [Neighbor<Met::Unit>],
+    ) -> &'b mut [Neighbor<Met::Unit>] {
+        self.search_layer_filtered(q, ef, 0, filter, searcher, dest)
+    }
+
+    /// Searches for the nearest neighbors of `q` on a particular layer like [`Hnsw::search_layer`].
+    pub fn search_layer<'b>(
+        &self,
+        q: &T,
+        ef: usize,
+        level: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'b mut [Neighbor<Met::Unit>],
+    ) -> &'b mut [Neighbor<Met::Unit>] {
+        self.search_layer_filtered(q, ef, level, |_| true, searcher, dest)
+    }
+
+    pub fn feature(&self, item: usize) -> &T {
+        &self.features[item]
+    }
+
+    pub fn layer_feature(&self, level: usize, item: usize) -> &T {
+        &self.features[self.layer_item_id(level, item)]
+    }
+
+    pub fn layer_item_id(&self, level: usize, item: usize) -> usize {
+        Graph::zero_node(self, level, item)
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers.len() + 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn layer_len(&self, level: usize) -> usize {
+        if level == 0 {
+            self.len()
+        } else if level < self.layers() {
+            self.layers[level - 1].len() / (M + 2)
+        } else {
+            0
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn is_removed(&self, item: usize) -> bool {
+        self.removed[item] != 0
+    }
+
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Gets the neighbors of a node on a layer like [`Hnsw::neighbors`].
+    pub fn neighbors(&self, level: usize, node: usize) -> impl Iterator<Item = usize> + 'a {
+        if level == 0 {
+            slots(&self.zero[node * M0..][..M0])
+        } else {
+            slots(&self.layers[level - 1][node * (M + 2) + 2..][..M])
+        }
+    }
+
+    fn search_layer_filtered<'b>(
+        &self,
+        q: &T,
+        ef: usize,
+        level: usize,
+        filter: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'b mut [Neighbor<Met::Unit>],
+    ) -> &'b mut [Neighbor<Met::Unit>] {
+        self.search_searcher(q, ef, level, filter, searcher);
+        let found = core::cmp::min(dest.len(), searcher.nearest.len());
+        dest[..found].copy_from_slice(&searcher.nearest[..found]);
+        &mut dest[..found]
+    }
+
+    /// Performs the same search as [`Hnsw::search_layer`], leaving the sorted results in `searcher.nearest`.
+    fn search_searcher(
+        &self,
+        q: &T,
+        ef: usize,
+        level: usize,
+        filter: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+    ) {
+        let search = GraphSearch {
+            metric: &self.metric,
+            features: self.features,
+            graph: self,
+        };
+        let accept = |item| !self.is_removed(item) && filter(item);
+        search.search(q, ef, level, self.layers.len(), accept, searcher);
+    }
+}
+
+impl<'a, Met, T, const M: usize, const M0: usize> Graph for FrozenHnsw<'a, Met, T, M, M0> {
+    type Slots<'b>
+        = Map<Iter<'b, u64>, fn(&u64) -> usize>
+    where
+        Self: 'b;
+
+    fn layers(&self) -> usize {
+        self.layers.len() + 1
+    }
+
+    fn layer_len(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.features.len()
+        } else {
+            self.layers[layer - 1].len() / (M + 2)
+        }
+    }
+
+    fn zero_node(&self, layer: usize, node: usize) -> usize {
+        if layer == 0 {
+            node
+        } else {
+            u64::from_le(self.layers[layer - 1][node * (M + 2)]) as usize
+        }
+    }
+
+    fn next_node(&self, layer: usize, node: usize) -> usize {
+        u64::from_le(self.layers[layer - 1][node * (M + 2) + 1]) as usize
+    }
+
+    fn slots(&self, layer: usize, node: usize) -> Self::Slots<'_> {
+        let slots = if layer == 0 {
+            &self.zero[node * M0..][..M0]
+        } else {
+            &self.layers[layer - 1][node * (M + 2) + 2..][..M]
+        };
+        slots.iter().map(slot)
+    }
+}
+
+/// Converts a little-endian neighbor slot, where empty slots are stored as `u64::MAX`, which is `!0` as a `usize`.
+fn slot(&n: &u64) -> usize {
+    u64::from_le(n) as usize
+}
+
+/// Gets the filled neighbor slots.
+fn slots(slots: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    slots.iter().map(slot).take_while(|&n| n != !0)
+}
+
+fn write_slots(writer: &mut impl Write, slots: &[usize]) -> std::io::Result<()> {
+    for &slot in slots {
+        write_u64(writer, if slot == !0 { u64::MAX } else { slot as u64 })?;
+    }
+    Ok(())
+}
+
+/// The number of bytes after `len` bytes to reach the next multiple of 8.
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+/// Splits data written with [`Hnsw::save_frozen`] into its sections.
+struct Sections<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Sections<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PersistError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(PersistError::Corrupt("data is too short"))?;
+        let section = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(section)
+    }
+
+    fn take_usize(&mut self) -> Result<usize, PersistError> {
+        let n = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(n).map_err(|_| PersistError::Corrupt("number too large for this platform"))
+    }
+
+    /// Takes a section of `len` values, where `None` means the length overflowed.
+    fn take_slice<U: Pod>(&mut self, len: Option<usize>) -> Result<&'a [U], PersistError> {
+        let bytes = len
+            .and_then(|len| len.checked_mul(size_of::<U>()))
+            .ok_or(PersistError::Corrupt("data is too short"))?;
+        bytemuck::try_cast_slice(self.take(bytes)?).map_err(|_| PersistError::Misaligned)
+    }
+}
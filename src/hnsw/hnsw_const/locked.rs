@@ -0,0 +1,455 @@
+use super::search::{Graph, GraphSearch};
+use crate::hnsw::nodes::{NeighborNodes, Node};
+use crate::*;
+use alloc::{vec, vec::Vec};
+use core::ops::Deref;
+use space::{Metric, Neighbor};
+use std::sync::{RwLock, RwLockReadGuard};
+
+/// A node in a non-zero layer with a lock on its neighbors.
+pub(super) struct LockedNode<const N: usize> {
+    zero_node: usize,
+    next_node: usize,
+    neighbors: RwLock<NeighborNodes<N>>,
+}
+
+impl<const N: usize> From<Node<N>> for LockedNode<N> {
+    fn from(node: Node<N>) -> Self {
+        Self {
+            zero_node: node.zero_node,
+            next_node: node.next_node,
+            neighbors: RwLock::new(node.neighbors),
+        }
+    }
+}
+
+impl<const N: usize> From<LockedNode<N>> for Node<N> {
+    fn from(node: LockedNode<N>) -> Self {
+        Self {
+            zero_node: node.zero_node,
+            next_node: node.next_node,
+            neighbors: node.neighbors.into_inner().unwrap(),
+        }
+    }
+}
+
+/// The graph of a [`Hnsw`] with a lock on the neighbors of every node, which allows the graph to be searched
+/// and changed from several threads at once.
+///
+/// Layers are numbered like they are in [`Hnsw`], so `0` is the zero layer and `n` is `layers[n - 1]`.
+/// A lock is never held while waiting on another lock, so this can't deadlock.
+pub(super) struct LockedGraph<const M: usize, const M0: usize> {
+    zero: Vec<RwLock<NeighborNodes<M0>>>,
+    layers: Vec<Vec<LockedNode<M>>>,
+}
+
+impl<const M: usize, const M0: usize> LockedGraph<M, M0> {
+    pub(super) fn new(zero: Vec<NeighborNodes<M0>>, layers: Vec<Vec<Node<M>>>) -> Self {
+        Self {
+            zero: zero.into_iter().map(RwLock::new).collect(),
+            layers: layers
+                .into_iter()
+                .map(|layer| layer.into_iter().map(LockedNode::from).collect())
+                .collect(),
+        }
+    }
+
+    pub(super) fn into_parts(self) -> (Vec<NeighborNodes<M0>>, Vec<Vec<Node<M>>>) {
+        let zero = self
+            .zero
+            .into_iter()
+            .map(|neighbors| neighbors.into_inner().unwrap())
+            .collect();
+        let layers = self
+            .layers
+            .into_iter()
+            .map(|layer| layer.into_iter().map(Node::from).collect())
+            .collect();
+        (zero, layers)
+    }
+
+    /// Adds the nodes of the next item on every layer up to `level` without any neighbors, adding layers
+    /// if needed, and returns its node on its top layer.
+    pub(super) fn push(&mut self, level: usize) -> usize {
+        let item = self.zero.len();
+        self.zero.push(RwLock::new(NeighborNodes {
+            neighbors: [!0; M0],
+        }));
+        let mut node = item;
+        for ix in 0..level {
+            if ix == self.layers.len() {
+                self.layers.push(vec![]);
+            }
+            let next_node = node;
+            node = self.layers[ix].len();
+            self.layers[ix].push(LockedNode {
+                zero_node: item,
+                next_node,
+                neighbors: RwLock::new(NeighborNodes { neighbors: [!0; M] }),
+            });
+        }
+        node
+    }
+
+    /// Locks the neighbors of a node for reading.
+    pub(super) fn read_neighbors(&self, layer: usize, node: usize) -> LockedNeighbors<'_, M, M0> {
+        if layer == 0 {
+            LockedNeighbors::Zero(self.zero[node].read().unwrap())
+        } else {
+            LockedNeighbors::NonZero(self.layers[layer - 1][node].neighbors.read().unwrap())
+        }
+    }
+
+    /// Replaces the neighbors of a node only if they are still `expected`, and returns whether they were replaced.
+    fn try_set_neighbors(
+        &self,
+        layer: usize,
+        node: usize,
+        expected: &[usize],
+        neighbors: &[usize],
+    ) -> bool {
+        fn try_set<const N: usize>(
+            lock: &RwLock<NeighborNodes<N>>,
+            expected: &[usize],
+            neighbors: &[usize],
+        ) -> bool {
+            let mut current = lock.write().unwrap();
+            if filled(&current.neighbors) != expected {
+                return false;
+            }
+            for (slot, neighbor) in current.neighbors.iter_mut().enumerate() {
+                *neighbor = neighbors.get(slot).copied().unwrap_or(!0);
+            }
+            true
+        }
+
+        if layer == 0 {
+            try_set(&self.zero[node], expected, neighbors)
+        } else {
+            try_set(&self.layers[layer - 1][node].neighbors, expected, neighbors)
+        }
+    }
+}
+
+impl<const M: usize, const M0: usize> Graph for LockedGraph<M, M0> {
+    type Slots<'a>
+        = LockedSlots<'a, M, M0>
+    where
+        Self: 'a;
+
+    fn layers(&self) -> usize {
+        self.layers.len() + 1
+    }
+
+    fn layer_len(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.zero.len()
+        } else {
+            self.layers[layer - 1].len()
+        }
+    }
+
+    fn zero_node(&self, layer: usize, node: usize) -> usize {
+        if layer == 0 {
+            node
+        } else {
+            self.layers[layer - 1][node].zero_node
+        }
+    }
+
+    fn next_node(&self, layer: usize, node: usize) -> usize {
+        self.layers[layer - 1][node].next_node
+    }
+
+    fn slots(&self, layer: usize, node: usize) -> LockedSlots<'_, M, M0> {
+        LockedSlots {
+            neighbors: self.read_neighbors(layer, node),
+            slot: 0,
+        }
+    }
+}
+
+/// The neighbors of a node in a [`LockedGraph`], which are locked for reading until this is dropped.
+pub(super) enum LockedNeighbors<'a, const M: usize, const M0: usize> {
+    Zero(RwLockReadGuard<'a, NeighborNodes<M0>>),
+    NonZero(RwLockReadGuard<'a, NeighborNodes<M>>),
+}
+
+impl<'a, const M: usize, const M0: usize> LockedNeighbors<'a, M, M0> {
+    /// Gets every neighbor slot, including the empty (`!0`) slots.
+    fn slots(&self) -> &[usize] {
+        match self {
+            LockedNeighbors::Zero(neighbors) => &neighbors.neighbors,
+            LockedNeighbors::NonZero(neighbors) => &neighbors.neighbors,
+        }
+    }
+}
+
+impl<'a, const M: usize, const M0: usize> Deref for LockedNeighbors<'a, M, M0> {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        filled(self.slots())
+    }
+}
+
+/// The neighbor slots of a node in a [`LockedGraph`], which are locked for reading until this is dropped.
+pub(super) struct LockedSlots<'a, const M: usize, const M0: usize> {
+    neighbors: LockedNeighbors<'a, M, M0>,
+    slot: usize,
+}
+
+impl<'a, const M: usize, const M0: usize> Iterator for LockedSlots<'a, M, M0> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let neighbor = self.neighbors.slots().get(self.slot).copied()?;
+        self.slot += 1;
+        Some(neighbor)
+    }
+}
+
+/// Searches a [`LockedGraph`] and links items into it.
+pub(super) struct Linker<'a, Met, T, S, const M: usize, const M0: usize> {
+    pub(super) metric: &'a Met,
+    pub(super) selector: &'a S,
+    pub(super) features: &'a [T],
+    pub(super) removed: &'a [bool],
+    pub(super) ef_construction: usize,
+    pub(super) graph: &'a LockedGraph<M, M0>,
+}
+
+impl<'a, Met, T, S, const M: usize, const M0: usize> Linker<'a, Met, T, S, M, M0>
+where
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Links an item into the graph, which works like [`Hnsw::insert`].
+    ///
+    /// The nodes of the item must already be in the graph without any neighbors, and `node` is its index on its
+    /// top layer. The search starts from the first node on layer `top`.
+    pub(super) fn insert(
+        &self,
+        item: usize,
+        level: usize,
+        node: usize,
+        top: usize,
+        searcher: &mut Searcher<Met::Unit>,
+    ) {
+        let q = &self.features[item];
+        // Find the nodes of the item on every layer it is in.
+        let mut nodes = vec![0; level + 1];
+        nodes[level] = node;
+        for layer in (1..=level).rev() {
+            nodes[layer - 1] = self.graph.layers[layer - 1][nodes[layer]].next_node;
+        }
+
+        let search = self.graph_search();
+        search.initialize_searcher(q, top, searcher);
+        let mut cap = if level >= top {
+            self.ef_construction
+        } else {
+            1
+        };
+
+        // Find the entry point on the level it was created by searching normally until its level.
+        for layer in (level + 1..=top).rev() {
+            search.search_single_layer(q, searcher, layer, cap, |_| true);
+            search.lower_search(layer, searcher);
+            cap = if layer == level + 1 {
+                self.ef_construction
+            } else {
+                1
+            };
+        }
+
+        // Then start from its level and find its nearest neighbors on each layer.
+        let mut nearest = vec![vec![]; core::cmp::min(level, top) + 1];
+        for layer in (1..nearest.len()).rev() {
+            search.search_single_layer(q, searcher, layer, cap, |_| true);
+            nearest[layer] = searcher.nearest.clone();
+            search.lower_search(layer, searcher);
+            cap = self.ef_construction;
+        }
+
+        // Also search the zero layer.
+        search.search_single_layer(q, searcher, 0, cap, |_| true);
+        nearest[0] = searcher.nearest.clone();
+
+        // Link the node from the zero layer up. Other threads can only reach it on a layer once it is linked
+        // there, so a search that reaches it never descends to one of its nodes which has no neighbors yet.
+        for (layer, nearest) in nearest.iter().enumerate() {
+            self.link(layer, nodes[layer], nearest);
+        }
+    }
+
+    /// Finds the approximate nearest neighbors of `q` in the zero layer like [`Hnsw::nearest`], leaving the
+    /// sorted results in `searcher.nearest`. The search starts from the first node on layer `top`.
+    pub(super) fn nearest(&self, q: &T, ef: usize, top: usize, searcher: &mut Searcher<Met::Unit>) {
+        self.graph_search()
+            .search(q, ef, 0, top, |item| !self.is_removed(item), searcher);
+    }
+
+    /// Searches the graph with the features of its items.
+    fn graph_search(&self) -> GraphSearch<'a, Met, T, LockedGraph<M, M0>> {
+        GraphSearch {
+            metric: self.metric,
+            features: self.features,
+            graph: self.graph,
+        }
+    }
+
+    /// Connects a node that isn't linked yet to the graph given its nearest neighbors in the layer.
+    ///
+    /// Other threads can reach the node before it is linked and add themselves to its neighbors, so its
+    /// neighbors are chosen from those as well. If they change in the meantime, this starts over.
+    fn link(&self, layer: usize, node: usize, nearest: &[Neighbor<Met::Unit>]) {
+        let max_neighbors = if layer == 0 { M0 } else { M };
+        let view = LockedLayer {
+            linker: self,
+            layer,
+        };
+        let feature = self.feature(layer, node);
+        let neighbors = loop {
+            let current = self.graph.read_neighbors(layer, node).to_vec();
+            // The node itself may have been found through the nodes already linked to it.
+            let mut candidates: Vec<_> = nearest
+                .iter()
+                .filter(|n| n.index != node)
+                .copied()
+                .collect();
+            for &n in &current {
+                if !candidates.iter().any(|c| c.index == n) {
+                    candidates.push(Neighbor {
+                        index: n,
+                        distance: self.metric.distance(feature, self.feature(layer, n)),
+                    });
+                }
+            }
+            candidates.sort_by_key(|n| n.distance);
+            let neighbors = self.selector.select_neighbors(
+                self.metric,
+                &view,
+                node,
+                &candidates,
+                max_neighbors,
+            );
+            let indices: Vec<usize> = neighbors.iter().map(|n| n.index).collect();
+            if self
+                .graph
+                .try_set_neighbors(layer, node, &current, &indices)
+            {
+                break neighbors;
+            }
+        };
+        for neighbor in neighbors {
+            self.add_neighbor(node, neighbor.index, neighbor.distance, layer);
+        }
+    }
+
+    /// Attempts to add a neighbor to a target node like [`Hnsw`] does when inserting.
+    ///
+    /// The new neighbors of the target are chosen without holding its lock, so if another thread changes
+    /// them in the meantime, this starts over.
+    fn add_neighbor(&self, node: usize, target: usize, distance: Met::Unit, layer: usize) {
+        let max_neighbors = if layer == 0 { M0 } else { M };
+        let new_neighbor = Neighbor {
+            index: node,
+            distance,
+        };
+        let view = LockedLayer {
+            linker: self,
+            layer,
+        };
+        loop {
+            let neighbors = self.graph.read_neighbors(layer, target).to_vec();
+            // The target may have linked itself to the node already.
+            if neighbors.contains(&node) {
+                return;
+            }
+            let new_neighbors: Vec<usize> = if neighbors.len() != max_neighbors
+                && self.selector.append_neighbor(
+                    self.metric,
+                    &view,
+                    target,
+                    new_neighbor,
+                    max_neighbors,
+                ) {
+                // There is an empty slot, so add it there.
+                neighbors.iter().copied().chain(Some(node)).collect()
+            } else {
+                // Otherwise, let the selector choose the neighbors of the target again including the new node.
+                let target_feature = self.feature(layer, target);
+                let distances: Vec<_> = neighbors
+                    .iter()
+                    .map(|&n| Neighbor {
+                        index: n,
+                        distance: self.metric.distance(target_feature, self.feature(layer, n)),
+                    })
+                    .collect();
+                self.selector
+                    .replace_neighbors(
+                        self.metric,
+                        &view,
+                        target,
+                        &distances,
+                        new_neighbor,
+                        max_neighbors,
+                    )
+                    .iter()
+                    .map(|n| n.index)
+                    .collect()
+            };
+            if self
+                .graph
+                .try_set_neighbors(layer, target, &neighbors, &new_neighbors)
+            {
+                return;
+            }
+        }
+    }
+
+    fn feature(&self, layer: usize, node: usize) -> &'a T {
+        &self.features[self.graph.zero_node(layer, node)]
+    }
+
+    fn is_removed(&self, item: usize) -> bool {
+        self.removed.get(item).copied().unwrap_or(false)
+    }
+}
+
+/// A single layer of a [`LockedGraph`] given to a [`NeighborSelector`].
+struct LockedLayer<'a, 'b, Met, T, S, const M: usize, const M0: usize> {
+    linker: &'a Linker<'b, Met, T, S, M, M0>,
+    layer: usize,
+}
+
+impl<'a, 'b, Met, T, S, const M: usize, const M0: usize> LayerView<T>
+    for LockedLayer<'a, 'b, Met, T, S, M, M0>
+where
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Neighbors<'c>
+        = LockedNeighbors<'c, M, M0>
+    where
+        Self: 'c;
+
+    fn feature(&self, node: usize) -> &T {
+        self.linker.feature(self.layer, node)
+    }
+
+    fn neighbors(&self, node: usize) -> LockedNeighbors<'_, M, M0> {
+        self.linker.graph.read_neighbors(self.layer, node)
+    }
+
+    fn is_removed(&self, node: usize) -> bool {
+        self.linker
+            .is_removed(self.linker.graph.zero_node(self.layer, node))
+    }
+}
+
+/// Gets the neighbor slots that are in use, which are always at the front.
+fn filled(neighbors: &[usize]) -> &[usize] {
+    &neighbors[..neighbors.partition_point(|&n| n != !0)]
+}
@@ -0,0 +1,193 @@
+use super::locked::{Linker, LockedGraph};
+use super::search::Graph;
+use crate::*;
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+use rand_core::{RngCore, SeedableRng};
+use rayon::prelude::*;
+use space::Metric;
+
+impl<Met, T, R, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0>
+where
+    R: RngCore + SeedableRng,
+    Met: Metric<T> + Sync,
+    T: Sync,
+{
+    /// Creates a HNSW from a batch of features, which are inserted in parallel.
+    ///
+    /// See [`Hnsw::insert_batch_parallel`].
+    pub fn from_batch_parallel(
+        metric: Met,
+        params: Params,
+        features: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let mut hnsw = Self::new_params(metric, params);
+        hnsw.insert_batch_parallel(features);
+        hnsw
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T> + Sync,
+    T: Sync,
+    S: NeighborSelector<Met, T> + Sync,
+{
+    /// Inserts a batch of features into the HNSW in parallel using rayon.
+    ///
+    /// The features get consecutive indices in the order they are given, and the range of those indices
+    /// is returned. The level of every feature is chosen up front, and then the features are linked into the
+    /// graph concurrently with a lock on the neighbors of every node. The graph depends on the order in which
+    /// the threads happen to link the features, so it is not reproducible like it is with [`Hnsw::insert`].
+    pub fn insert_batch_parallel(&mut self, features: impl IntoIterator<Item = T>) -> Range<usize> {
+        let start = self.zero.len();
+        let start_layers = self.layers.len();
+        let mut graph = LockedGraph::new(
+            core::mem::take(&mut self.zero),
+            core::mem::take(&mut self.layers),
+        );
+
+        // Add every feature and its nodes first so that each item has a fixed index on every layer.
+        // This keeps the `(level, node)` of each new item, where `node` is its index on its top layer.
+        let mut tops = vec![];
+        for q in features {
+            let level = self.random_level();
+            self.features.push(q);
+            tops.push((level, graph.push(level)));
+        }
+        let items = start..self.features.len();
+
+        if !items.is_empty() {
+            let entry = graph.zero_node(graph.layers() - 1, 0);
+            let linker = Linker {
+                metric: &self.metric,
+                selector: &self.selector,
+                features: &self.features,
+                removed: &self.removed,
+                ef_construction: self.params.ef_construction,
+                graph: &graph,
+            };
+
+            // If there is a new entry point, it must be linked before everything else since every search starts
+            // there. It can only be found from the old entry point, so it is linked using only the old layers.
+            if entry >= start && start != 0 {
+                let (level, node) = tops[entry - start];
+                linker.insert(entry, level, node, start_layers, &mut Searcher::default());
+            }
+            let top = graph.layers() - 1;
+            items
+                .clone()
+                .into_par_iter()
+                .filter(|&item| item != entry)
+                .for_each_init(Searcher::default, |searcher, item| {
+                    let (level, node) = tops[item - start];
+                    linker.insert(item, level, node, top, searcher);
+                });
+        }
+
+        let (zero, layers) = graph.into_parts();
+        self.zero = zero;
+        self.layers = layers;
+        if self.params.inbound_edges {
+            self.rebuild_inbound();
+        }
+        items
+    }
+}
+
+impl<Met, T, R, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0>
+where
+    R: RngCore + SeedableRng + Sync,
+    Met: Metric<T> + Sync,
+    Met::Unit: Send,
+    T: Sync,
+{
+    /// Creates a HNSW from a batch of features, which are inserted in parallel, producing the same graph
+    /// every time.
+    ///
+    /// See [`Hnsw::insert_batch_deterministic`].
+    pub fn from_batch_deterministic(
+        metric: Met,
+        params: Params,
+        features: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let mut hnsw = Self::new_params(metric, params);
+        hnsw.insert_batch_deterministic(features);
+        hnsw
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore + Sync,
+    Met: Metric<T> + Sync,
+    Met::Unit: Send,
+    T: Sync,
+    S: NeighborSelector<Met, T> + Sync,
+{
+    /// Inserts a batch of features into the HNSW in parallel using rayon, producing the same graph every time.
+    ///
+    /// Unlike [`Hnsw::insert_batch_parallel`], the graph doesn't depend on the number of threads or how they
+    /// are scheduled. The level of every feature comes from the PRNG in order just like [`Hnsw::insert`].
+    /// The features are then inserted in rounds: every feature in a round searches the graph as it was at the
+    /// start of the round in parallel, and then they are linked into the graph in order. Features in the same
+    /// round can't link to each other directly, so the graph is not the same as inserting them one at a time.
+    ///
+    /// The features get consecutive indices in the order they are given, and the range of those indices
+    /// is returned.
+    pub fn insert_batch_deterministic(
+        &mut self,
+        features: impl IntoIterator<Item = T>,
+    ) -> Range<usize> {
+        let start = self.zero.len();
+        let mut features = features.into_iter();
+        let mut searcher = Searcher::default();
+        loop {
+            let round_size = (self.len() / ROUND_FRACTION).clamp(1, MAX_ROUND);
+            let mut round = vec![];
+            let mut alone = None;
+            for q in &mut features {
+                let level = self.random_level();
+                // A feature that adds layers changes the entry point, so it is inserted on its own after the round.
+                if self.is_empty() || level > self.layers.len() {
+                    alone = Some((q, level));
+                    break;
+                }
+                round.push((q, level));
+                if round.len() == round_size {
+                    break;
+                }
+            }
+            if round.is_empty() && alone.is_none() {
+                break;
+            }
+
+            // Search for the neighbors of every feature in parallel.
+            let hnsw = &*self;
+            let nearest: Vec<_> = round
+                .par_iter()
+                .map_init(Searcher::default, |searcher, (q, level)| {
+                    hnsw.search_insertion(q, *level, searcher)
+                })
+                .collect();
+            // Then link them in order.
+            for ((q, level), nearest) in round.into_iter().zip(nearest) {
+                self.features.push(q);
+                self.link_insertion(&nearest, level);
+            }
+            if let Some((q, level)) = alone {
+                self.insert_level(q, level, &mut searcher);
+            }
+        }
+        start..self.zero.len()
+    }
+}
+
+/// Features in the same round of [`Hnsw::insert_batch_deterministic`] can't find each other, so a round only
+/// has up to this fraction of the items already in the graph.
+const ROUND_FRACTION: usize = 16;
+
+/// The maximum number of features in a round of [`Hnsw::insert_batch_deterministic`], which limits the memory
+/// used to hold their nearest neighbors.
+const MAX_ROUND: usize = 4096;
@@ -0,0 +1,347 @@
+use crate::hnsw::nodes::{NeighborNodes, Node};
+use crate::*;
+use alloc::{vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt;
+use rand_core::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use space::Metric;
+use std::io::{self, Read, Write};
+
+/// The first bytes of every saved HNSW.
+pub const MAGIC: [u8; 8] = *b"HNSWGRPH";
+
+/// The version of the formats written by [`Hnsw::save`] and `Hnsw::save_frozen`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// An error while saving or loading a HNSW with [`Hnsw::save`] or [`Hnsw::load`].
+#[derive(Debug)]
+pub enum PersistError {
+    /// The reader or writer failed.
+    Io(io::Error),
+    /// The data doesn't start with [`MAGIC`], so it isn't a saved HNSW.
+    BadMagic,
+    /// The data was saved in a version of the format this can't load.
+    UnsupportedVersion(u32),
+    /// The data was saved from a HNSW with a different `M`.
+    MismatchedM { expected: usize, found: usize },
+    /// The data was saved from a HNSW with a different `M0`.
+    MismatchedM0 { expected: usize, found: usize },
+    /// The data was saved with features of a different size.
+    MismatchedFeatureSize { expected: usize, found: usize },
+    /// The data isn't aligned to 8 bytes in memory, so it can't be used without copying it.
+    Misaligned,
+    /// The metric, features, PRNG or neighbor selector couldn't be encoded or decoded.
+    Encoding(bincode::Error),
+    /// The data is not consistent with itself.
+    Corrupt(&'static str),
+    /// The graph that was loaded is broken.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(e) => write!(f, "i/o error: {}", e),
+            PersistError::BadMagic => write!(f, "not a saved HNSW (bad magic number)"),
+            PersistError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            PersistError::MismatchedM { expected, found } => {
+                write!(
+                    f,
+                    "saved with M = {}, but loaded with M = {}",
+                    found, expected
+                )
+            }
+            PersistError::MismatchedM0 { expected, found } => {
+                write!(
+                    f,
+                    "saved with M0 = {}, but loaded with M0 = {}",
+                    found, expected
+                )
+            }
+            PersistError::MismatchedFeatureSize { expected, found } => write!(
+                f,
+                "saved with {} byte features, but loaded with {} byte features",
+                found, expected
+            ),
+            PersistError::Misaligned => write!(f, "data is not aligned to 8 bytes"),
+            PersistError::Encoding(e) => write!(f, "encoding error: {}", e),
+            PersistError::Corrupt(reason) => write!(f, "corrupt data: {}", reason),
+            PersistError::Invalid(e) => write!(f, "invalid graph: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PersistError::Io(e) => Some(e),
+            PersistError::Encoding(e) => Some(e),
+            PersistError::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PersistError {
+    fn from(e: io::Error) -> Self {
+        PersistError::Io(e)
+    }
+}
+
+impl From<ValidationError> for PersistError {
+    fn from(e: ValidationError) -> Self {
+        PersistError::Invalid(e)
+    }
+}
+
+impl From<bincode::Error> for PersistError {
+    fn from(e: bincode::Error) -> Self {
+        PersistError::Encoding(e)
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore + Serialize,
+    Met: Metric<T> + Serialize,
+    T: Serialize,
+    S: NeighborSelector<Met, T> + Serialize,
+{
+    /// Writes the HNSW in a binary format that can be read with [`Hnsw::load`].
+    ///
+    /// The format starts with a header recording the format version, `M`, `M0`, the size of every layer
+    /// and the [`Params`]. The graph follows as little-endian integers, and then the metric, PRNG, neighbor
+    /// selector and features are encoded with `bincode`. The inbound edges are not saved.
+    ///
+    /// This performs many small writes, so the writer should be buffered.
+    pub fn save(&self, mut writer: impl Write) -> Result<(), PersistError> {
+        let writer = &mut writer;
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_u64(writer, M as u64)?;
+        write_u64(writer, M0 as u64)?;
+        write_u64(writer, self.zero.len() as u64)?;
+        write_u64(writer, self.layers.len() as u64)?;
+        for layer in &self.layers {
+            write_u64(writer, layer.len() as u64)?;
+        }
+        write_u64(writer, self.tombstones as u64)?;
+        write_params(writer, &self.params)?;
+
+        for node in &self.zero {
+            write_neighbors(writer, &node.neighbors)?;
+        }
+        for layer in &self.layers {
+            for node in layer {
+                write_u64(writer, node.zero_node as u64)?;
+                write_u64(writer, node.next_node as u64)?;
+                write_neighbors(writer, &node.neighbors.neighbors)?;
+            }
+        }
+        for item in 0..self.zero.len() {
+            writer.write_all(&[self.is_removed(item) as u8])?;
+        }
+
+        write_encoded(writer, &self.metric)?;
+        write_encoded(writer, &self.prng)?;
+        write_encoded(writer, &self.selector)?;
+        write_encoded(writer, &self.features)?;
+        Ok(())
+    }
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore + DeserializeOwned,
+    Met: Metric<T> + DeserializeOwned,
+    T: DeserializeOwned,
+    S: NeighborSelector<Met, T> + DeserializeOwned,
+{
+    /// Reads a HNSW written with [`Hnsw::save`].
+    ///
+    /// This fails with a [`PersistError`] describing the problem if the data was saved with a different `M` or
+    /// `M0`, in a different version of the format, or is not a saved HNSW at all.
+    ///
+    /// This performs many small reads, so the reader should be buffered.
+    pub fn load(mut reader: impl Read) -> Result<Self, PersistError> {
+        let reader = &mut reader;
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(PersistError::BadMagic);
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(PersistError::UnsupportedVersion(version));
+        }
+        let found = read_usize(reader)?;
+        if found != M {
+            return Err(PersistError::MismatchedM { expected: M, found });
+        }
+        let found = read_usize(reader)?;
+        if found != M0 {
+            return Err(PersistError::MismatchedM0 {
+                expected: M0,
+                found,
+            });
+        }
+        let len = read_usize(reader)?;
+        let layer_lens = (0..read_usize(reader)?)
+            .map(|_| read_usize(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tombstones = read_usize(reader)?;
+        let params = read_params(reader)?;
+
+        let zero = (0..len)
+            .map(|_| {
+                Ok(NeighborNodes {
+                    neighbors: read_neighbors(reader)?,
+                })
+            })
+            .collect::<Result<Vec<_>, PersistError>>()?;
+        let layers = layer_lens
+            .iter()
+            .map(|&layer_len| {
+                (0..layer_len)
+                    .map(|_| {
+                        Ok(Node {
+                            zero_node: read_usize(reader)?,
+                            next_node: read_usize(reader)?,
+                            neighbors: NeighborNodes {
+                                neighbors: read_neighbors(reader)?,
+                            },
+                        })
+                    })
+                    .collect::<Result<Vec<_>, PersistError>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut removed = vec![0; len];
+        reader.read_exact(&mut removed)?;
+        let removed: Vec<bool> = removed.into_iter().map(|removed| removed != 0).collect();
+
+        let metric = read_encoded(reader)?;
+        let prng = read_encoded(reader)?;
+        let selector = read_encoded(reader)?;
+        let features = read_encoded(reader)?;
+
+        let mut hnsw = Self {
+            metric,
+            zero,
+            features,
+            layers,
+            prng,
+            params: params.inbound_edges(false),
+            removed,
+            tombstones,
+            zero_inbound: vec![],
+            layers_inbound: vec![],
+            selector,
+        };
+        // The inbound edges can only be rebuilt once the graph is known to be valid.
+        hnsw.validate()?;
+        if params.inbound_edges {
+            hnsw.params = params;
+            hnsw.rebuild_inbound();
+        }
+        Ok(hnsw)
+    }
+}
+
+pub(super) fn write_u64(writer: &mut impl Write, n: u64) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_usize(reader: &mut impl Read) -> Result<usize, PersistError> {
+    usize::try_from(read_u64(reader)?)
+        .map_err(|_| PersistError::Corrupt("number too large for this platform"))
+}
+
+/// Empty neighbor slots (`!0`) are always written as `u64::MAX` regardless of the size of `usize`.
+fn write_neighbors(writer: &mut impl Write, neighbors: &[usize]) -> io::Result<()> {
+    for &neighbor in neighbors {
+        write_u64(
+            writer,
+            if neighbor == !0 {
+                u64::MAX
+            } else {
+                neighbor as u64
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn read_neighbors<const N: usize>(reader: &mut impl Read) -> Result<[usize; N], PersistError> {
+    let mut neighbors = [!0; N];
+    for neighbor in &mut neighbors {
+        let n = read_u64(reader)?;
+        if n != u64::MAX {
+            *neighbor = usize::try_from(n)
+                .map_err(|_| PersistError::Corrupt("number too large for this platform"))?;
+        }
+    }
+    Ok(neighbors)
+}
+
+fn write_params(writer: &mut impl Write, params: &Params) -> io::Result<()> {
+    write_u64(writer, params.ef_construction as u64)?;
+    let selection = match params.neighbor_selection {
+        NeighborSelection::Simple => [0, 0, 0],
+        NeighborSelection::Heuristic {
+            extend_candidates,
+            keep_pruned_connections,
+        } => [1, extend_candidates as u8, keep_pruned_connections as u8],
+    };
+    writer.write_all(&[params.inbound_edges as u8])?;
+    writer.write_all(&selection)
+}
+
+fn read_params(reader: &mut impl Read) -> Result<Params, PersistError> {
+    let ef_construction = read_usize(reader)?;
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    let [inbound_edges, selection, extend_candidates, keep_pruned_connections] = bytes;
+    let neighbor_selection = match selection {
+        0 => NeighborSelection::Simple,
+        1 => NeighborSelection::Heuristic {
+            extend_candidates: extend_candidates != 0,
+            keep_pruned_connections: keep_pruned_connections != 0,
+        },
+        _ => return Err(PersistError::Corrupt("unknown neighbor selection")),
+    };
+    Ok(Params::new()
+        .ef_construction(ef_construction)
+        .inbound_edges(inbound_edges != 0)
+        .neighbor_selection(neighbor_selection))
+}
+
+/// Writes a value encoded with `bincode` after its length in bytes.
+fn write_encoded(writer: &mut impl Write, value: &impl Serialize) -> Result<(), PersistError> {
+    write_u64(writer, bincode::serialized_size(value)?)?;
+    bincode::serialize_into(writer, value)?;
+    Ok(())
+}
+
+/// Reads a value written with [`write_encoded`], which must use every byte.
+fn read_encoded<V: DeserializeOwned>(reader: &mut impl Read) -> Result<V, PersistError> {
+    let size = read_u64(reader)?;
+    let mut section = reader.take(size);
+    let value = bincode::deserialize_from(&mut section)?;
+    if section.limit() != 0 {
+        return Err(PersistError::Corrupt(
+            "encoded value is shorter than its size",
+        ));
+    }
+    Ok(value)
+}
@@ -0,0 +1,180 @@
+use crate::*;
+use alloc::{vec, vec::Vec};
+use rand_core::RngCore;
+use space::Metric;
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Finds the nodes on every layer which can't be reached by following edges from the entry point on that
+    /// layer. These usually have no inbound edges, since the nodes they were linked to when they were inserted
+    /// kept closer neighbors instead.
+    ///
+    /// An unreachable item can only be found by [`Hnsw::nearest`] if the search happens to start near it on
+    /// the layer above, so it is usually never found. Use [`Hnsw::repair`] to link them back into the graph.
+    ///
+    /// Returns the unreachable nodes of each layer, starting with the zero layer where the nodes are items.
+    pub fn find_unreachable(&self) -> Vec<Vec<usize>> {
+        (0..self.layers())
+            .map(|level| {
+                self.reachable(level)
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &reachable)| !reachable)
+                    .map(|(node, _)| node)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Links every unreachable node which hasn't been removed back into the graph, so that it can be reached
+    /// from the entry point on its layer. See [`Hnsw::find_unreachable`].
+    ///
+    /// Each unreachable node becomes a neighbor of the closest reachable node with an empty neighbor slot. If
+    /// the closest reachable nodes are all full, the nearest one replaces the neighbor closest to the
+    /// unreachable node with it, and that neighbor becomes a neighbor of the unreachable node instead. This
+    /// keeps everything that was reachable reachable, so afterwards every item that wasn't removed is.
+    ///
+    /// Returns the number of nodes which were linked back into the graph.
+    pub fn repair(&mut self, searcher: &mut Searcher<Met::Unit>) -> usize {
+        (0..self.layers())
+            .rev()
+            .map(|level| self.repair_layer(level, searcher))
+            .sum()
+    }
+
+    fn repair_layer(&mut self, level: usize, searcher: &mut Searcher<Met::Unit>) -> usize {
+        // This is indexed by item rather than node, since that is what searches are filtered by.
+        let mut reachable = vec![false; self.len()];
+        for (node, _) in self
+            .reachable(level)
+            .iter()
+            .enumerate()
+            .filter(|&(_, &reachable)| reachable)
+        {
+            reachable[self.layer_item_id(level, node)] = true;
+        }
+
+        let mut linked = 0;
+        for orphan in 0..self.layer_len(level) {
+            let item = self.layer_item_id(level, orphan);
+            // An earlier orphan may have led to this one.
+            if reachable[item] || self.is_removed(item) {
+                continue;
+            }
+            self.link_orphan(level, orphan, &reachable, searcher);
+            linked += 1;
+
+            // Everything the orphan leads to is now reachable as well.
+            let mut stack = vec![orphan];
+            reachable[item] = true;
+            while let Some(node) = stack.pop() {
+                for neighbor in self.neighbors(level, node) {
+                    let item = self.layer_item_id(level, neighbor);
+                    if !reachable[item] {
+                        reachable[item] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        linked
+    }
+
+    /// Makes an orphan the neighbor of a nearby reachable node on a layer.
+    fn link_orphan(
+        &mut self,
+        level: usize,
+        orphan: usize,
+        reachable: &[bool],
+        searcher: &mut Searcher<Met::Unit>,
+    ) {
+        let q = self.layer_feature(level, orphan);
+        self.search_searcher(
+            q,
+            self.params.ef_construction,
+            level,
+            |item| reachable[item],
+            searcher,
+        );
+        let mut candidates: Vec<usize> = searcher.nearest.iter().map(|n| n.index).collect();
+        if candidates.is_empty() {
+            // Every reachable node was removed, but the entry point can still route to the orphan.
+            candidates.extend(self.layer_entry(level));
+        }
+
+        for &node in &candidates {
+            let neighbors = self.layer_neighbors(level, node);
+            if let Some(slot) = neighbors.iter().position(|&n| n == !0) {
+                self.set_neighbor(level, node, slot, orphan);
+                return;
+            }
+        }
+
+        // Route the nearest node through the orphan to its neighbor that is closest to the orphan.
+        let node = candidates[0];
+        let neighbors = self.layer_neighbors(level, node);
+        let slot = (0..neighbors.len())
+            .min_by_key(|&slot| {
+                self.metric
+                    .distance(q, self.layer_feature(level, neighbors[slot]))
+            })
+            .unwrap();
+        let bypassed = neighbors[slot];
+
+        let orphan_neighbors = self.layer_neighbors(level, orphan);
+        let orphan_slot = if orphan_neighbors.contains(&bypassed) {
+            None
+        } else if let Some(slot) = orphan_neighbors.iter().position(|&n| n == !0) {
+            Some(slot)
+        } else {
+            // The orphan gives up its farthest neighbor, preferring one that is reachable anyway.
+            (0..orphan_neighbors.len()).max_by_key(|&slot| {
+                let neighbor = orphan_neighbors[slot];
+                (
+                    reachable[self.layer_item_id(level, neighbor)],
+                    self.metric.distance(q, self.layer_feature(level, neighbor)),
+                )
+            })
+        };
+
+        self.set_neighbor(level, node, slot, orphan);
+        if let Some(orphan_slot) = orphan_slot {
+            self.set_neighbor(level, orphan, orphan_slot, bypassed);
+        }
+    }
+
+    /// Gets the node of the entry point on a layer, or `None` if the layer is empty.
+    pub(super) fn layer_entry(&self, level: usize) -> Option<usize> {
+        if self.is_empty() || level >= self.layers() {
+            return None;
+        }
+        // The entry point is the first node on the top layer, and its next nodes lead down to the others.
+        let mut node = 0;
+        for layer in self.layers[level..].iter().rev() {
+            node = layer[node].next_node;
+        }
+        Some(node)
+    }
+
+    /// Finds which nodes on a layer can be reached by following edges from the entry point on that layer.
+    pub(super) fn reachable(&self, level: usize) -> Vec<bool> {
+        let mut reachable = vec![false; self.layer_len(level)];
+        let mut stack: Vec<usize> = self.layer_entry(level).into_iter().collect();
+        for &node in &stack {
+            reachable[node] = true;
+        }
+        while let Some(node) = stack.pop() {
+            for neighbor in self.neighbors(level, node) {
+                if !reachable[neighbor] {
+                    reachable[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        reachable
+    }
+}
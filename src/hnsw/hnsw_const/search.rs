@@ -0,0 +1,173 @@
+use crate::*;
+use core::iter::TakeWhile;
+use space::{Metric, Neighbor};
+
+/// The layers of a HNSW however they are stored, so that they are all searched and checked the same way.
+///
+/// Layers are numbered like in [`Hnsw::search_layer`], so `0` is the zero layer, where the nodes are items.
+pub(super) trait Graph {
+    /// The neighbor slots of a node.
+    type Slots<'a>: Iterator<Item = usize>
+    where
+        Self: 'a;
+
+    /// The number of layers including the zero layer.
+    fn layers(&self) -> usize;
+
+    fn layer_len(&self, layer: usize) -> usize;
+
+    /// Gets the zero layer node (the item) that a node on a layer refers to.
+    fn zero_node(&self, layer: usize, node: usize) -> usize;
+
+    /// Gets the node on the layer below that a node on a non-zero layer refers to.
+    fn next_node(&self, layer: usize, node: usize) -> usize;
+
+    /// Gets the neighbor slots of a node, including the empty (`!0`) slots, which are always at the end.
+    fn slots(&self, layer: usize, node: usize) -> Self::Slots<'_>;
+
+    /// Gets the neighbors of a node.
+    fn neighbors(
+        &self,
+        layer: usize,
+        node: usize,
+    ) -> TakeWhile<Self::Slots<'_>, fn(&usize) -> bool> {
+        self.slots(layer, node).take_while(|&n| n != !0)
+    }
+}
+
+/// Searches a [`Graph`] with the features of its items.
+pub(super) struct GraphSearch<'a, Met, T, G> {
+    pub(super) metric: &'a Met,
+    pub(super) features: &'a [T],
+    pub(super) graph: &'a G,
+}
+
+impl<'a, Met, T, G> GraphSearch<'a, Met, T, G>
+where
+    Met: Metric<T>,
+    G: Graph,
+{
+    /// Performs the search of [`Hnsw::search_layer`] starting from the first node on layer `top`, leaving the
+    /// sorted results in `searcher.nearest`.
+    ///
+    /// Only items for which `accept` returns `true` are placed into the nearest neighbors.
+    pub(super) fn search(
+        &self,
+        q: &T,
+        ef: usize,
+        level: usize,
+        top: usize,
+        accept: impl Fn(usize) -> bool,
+        searcher: &mut Searcher<Met::Unit>,
+    ) {
+        // If there is nothing in here, then just return nothing.
+        if self.graph.layer_len(0) == 0 || level > top {
+            searcher.clear();
+            return;
+        }
+
+        self.initialize_searcher(q, top, searcher);
+        for layer in (1..=top).rev() {
+            if layer == level {
+                // Items are only excluded on the layer being searched so they can still route the search.
+                self.search_single_layer(q, searcher, layer, 1, &accept);
+                return;
+            }
+            self.search_single_layer(q, searcher, layer, 1, |_| true);
+            self.lower_search(layer, searcher);
+        }
+
+        // search the zero layer
+        self.search_single_layer(q, searcher, 0, ef, &accept);
+    }
+
+    /// Greedily finds the approximate nearest neighbors to `q` in a layer.
+    /// This corresponds to Algorithm 2 in the paper.
+    ///
+    /// Only items for which `accept` returns `true` are placed into the nearest neighbors.
+    /// Items which are not accepted are still traversed if they would have been placed in the nearest neighbors.
+    pub(super) fn search_single_layer(
+        &self,
+        q: &T,
+        searcher: &mut Searcher<Met::Unit>,
+        layer: usize,
+        cap: usize,
+        accept: impl Fn(usize) -> bool,
+    ) {
+        // The starting point may not be accepted, but it is still in the candidates so it will be traversed.
+        searcher
+            .nearest
+            .retain(|n| accept(self.graph.zero_node(layer, n.index)));
+        while let Some(Neighbor { index, .. }) = searcher.candidates.pop() {
+            for neighbor in self.graph.neighbors(layer, index) {
+                let node_to_visit = self.graph.zero_node(layer, neighbor);
+
+                // Don't visit previously visited things. We use the zero node to allow reusing the seen filter
+                // across all layers since zero nodes are consistent among all layers.
+                // TODO: Use Cuckoo Filter or Bloom Filter to speed this up/take less memory.
+                if searcher.seen.insert(node_to_visit) {
+                    // Compute the distance of this neighbor.
+                    let distance = self.metric.distance(q, &self.features[node_to_visit]);
+                    // Attempt to insert into nearest queue.
+                    let pos = searcher.nearest.partition_point(|n| n.distance <= distance);
+                    if pos != cap {
+                        let candidate = Neighbor {
+                            index: neighbor,
+                            distance,
+                        };
+                        if accept(node_to_visit) {
+                            // It was successful. Now we need to know if its full.
+                            if searcher.nearest.len() == cap {
+                                // In this case remove the worst item.
+                                searcher.nearest.pop();
+                            }
+                            // Either way, add the new item.
+                            searcher.nearest.insert(pos, candidate);
+                        }
+                        searcher.candidates.push(candidate);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ready a search for the layer below a non-zero `layer`.
+    pub(super) fn lower_search(&self, layer: usize, searcher: &mut Searcher<Met::Unit>) {
+        // Clear the candidates so we can fill them with the best nodes in the last layer.
+        searcher.candidates.clear();
+        // Only preserve the best candidate. The original paper's algorithm uses `1` every time.
+        // See Algorithm 5 line 5 of the paper. The paper makes no further comment on why `1` was chosen.
+        let &Neighbor { index, distance } = searcher.nearest.first().unwrap();
+        searcher.nearest.clear();
+        // Update the node to the next layer.
+        let candidate = Neighbor {
+            index: self.graph.next_node(layer, index),
+            distance,
+        };
+        // Insert the index of the nearest neighbor into the nearest pool for the next layer.
+        searcher.nearest.push(candidate);
+        // Insert the index into the candidate pool as well.
+        searcher.candidates.push(candidate);
+    }
+
+    /// Resets a searcher and adds the entry point, which is the first node on layer `top`.
+    /// Must be passed the query element `q`.
+    pub(super) fn initialize_searcher(
+        &self,
+        q: &T,
+        top: usize,
+        searcher: &mut Searcher<Met::Unit>,
+    ) {
+        // Clear the searcher.
+        searcher.clear();
+        // Add the entry point.
+        let entry = self.graph.zero_node(top, 0);
+        let candidate = Neighbor {
+            index: 0,
+            distance: self.metric.distance(q, &self.features[entry]),
+        };
+        searcher.candidates.push(candidate);
+        searcher.nearest.push(candidate);
+        searcher.seen.insert(entry);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::*;
+use alloc::{vec, vec::Vec};
+use num_traits::ToPrimitive;
+use rand_core::RngCore;
+use space::Metric;
+
+/// The shape of the graph of a [`Hnsw`], returned by [`Hnsw::stats`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HnswStats {
+    /// The statistics of each layer, starting with the zero layer.
+    pub layers: Vec<LayerStats>,
+}
+
+/// The shape of a single layer of a [`Hnsw`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerStats {
+    /// The number of nodes on the layer.
+    pub nodes: usize,
+    /// The number of edges on the layer, which is the number of filled neighbor slots.
+    pub edges: usize,
+    /// The number of nodes with each number of neighbors, from `0` up to the number of neighbor slots on the
+    /// layer. Nodes with fewer neighbors than slots have their remaining slots empty.
+    pub out_degrees: Vec<usize>,
+    /// The number of nodes that are the neighbor of each number of other nodes, from `0` up to the most any
+    /// node has. Nodes with an in-degree of `0` can only be found by starting a search at them.
+    pub in_degrees: Vec<usize>,
+    /// The number of nodes that can't be reached by following edges from the entry point on this layer.
+    pub unreachable: usize,
+    /// The mean distance between the features of the two ends of every edge, or `None` without edges.
+    pub mean_edge_distance: Option<f64>,
+}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    Met::Unit: ToPrimitive,
+    S: NeighborSelector<Met, T>,
+{
+    /// Computes statistics about the shape of the graph on every layer, which helps to diagnose poor recall.
+    ///
+    /// This visits every node and edge and computes the distance of every edge, so it is expensive.
+    pub fn stats(&self) -> HnswStats {
+        HnswStats {
+            layers: (0..self.layers.len() + 1)
+                .map(|level| self.layer_stats(level))
+                .collect(),
+        }
+    }
+
+    fn layer_stats(&self, level: usize) -> LayerStats {
+        let (nodes, slots) = if level == 0 {
+            (self.zero.len(), M0)
+        } else {
+            (self.layers[level - 1].len(), M)
+        };
+        let mut out_degrees = vec![0; slots + 1];
+        let mut in_degree = vec![0usize; nodes];
+        let mut edges = 0;
+        let mut total_distance = 0.0;
+        for node in 0..nodes {
+            let feature = self.layer_feature(level, node);
+            let mut degree = 0;
+            for neighbor in self.neighbors(level, node) {
+                degree += 1;
+                in_degree[neighbor] += 1;
+                let distance = self
+                    .metric
+                    .distance(feature, self.layer_feature(level, neighbor));
+                total_distance += distance.to_f64().unwrap_or(f64::NAN);
+            }
+            out_degrees[degree] += 1;
+            edges += degree;
+        }
+
+        let mut in_degrees = vec![0; in_degree.iter().copied().max().map_or(0, |max| max + 1)];
+        for &degree in &in_degree {
+            in_degrees[degree] += 1;
+        }
+
+        LayerStats {
+            nodes,
+            edges,
+            out_degrees,
+            in_degrees,
+            unreachable: self.reachable(level).iter().filter(|&&r| !r).count(),
+            mean_edge_distance: if edges == 0 {
+                None
+            } else {
+                Some(total_distance / edges as f64)
+            },
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use super::search::Graph;
+use crate::*;
+use alloc::{vec, vec::Vec};
+use core::fmt;
+#[cfg(feature = "serde")]
+use {
+    crate::hnsw::nodes::{InboundNodes, NeighborNodes, Node},
+    core::convert::TryFrom,
+    serde::Deserialize,
+};
+
+/// A broken invariant in the graph of a [`Hnsw`] found by [`Hnsw::validate`].
+///
+/// Levels and nodes are numbered like in [`Hnsw::search_layer`], so on level `0` the nodes are items.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// There isn't exactly one feature for every item.
+    FeatureCount { features: usize, items: usize },
+    /// More items are marked as removed than there are items.
+    RemovedCount { removed: usize, items: usize },
+    /// The number of tombstones doesn't match the number of items marked as removed.
+    TombstoneCount { tombstones: usize, removed: usize },
+    /// A layer above the zero layer has no nodes, so there is no entry point on it.
+    EmptyLayer { level: usize },
+    /// A node refers to an item that doesn't exist.
+    ItemOutOfRange {
+        level: usize,
+        node: usize,
+        item: usize,
+    },
+    /// Two nodes on a layer refer to the same item.
+    DuplicateItem { level: usize, item: usize },
+    /// A node refers to a node on the layer below that doesn't exist.
+    NextNodeOutOfRange {
+        level: usize,
+        node: usize,
+        next_node: usize,
+    },
+    /// A node refers to a node on the layer below that is for a different item.
+    NextNodeMismatch { level: usize, node: usize },
+    /// A node has a neighbor that doesn't exist on its layer.
+    NeighborOutOfRange {
+        level: usize,
+        node: usize,
+        neighbor: usize,
+    },
+    /// A node is its own neighbor.
+    SelfNeighbor { level: usize, node: usize },
+    /// A node has the same neighbor twice.
+    DuplicateNeighbor {
+        level: usize,
+        node: usize,
+        neighbor: usize,
+    },
+    /// A node has a neighbor after an empty neighbor slot, where it would be ignored.
+    NeighborAfterEmptySlot { level: usize, node: usize },
+    /// The inbound edges of a node don't match the neighbors of the other nodes.
+    InboundEdges { level: usize, node: usize },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ValidationError::FeatureCount { features, items } => {
+                write!(f, "there are {} features for {} items", features, items)
+            }
+            ValidationError::RemovedCount { removed, items } => write!(
+                f,
+                "{} items are marked as removed, but there are only {} items",
+                removed, items
+            ),
+            ValidationError::TombstoneCount {
+                tombstones,
+                removed,
+            } => write!(
+                f,
+                "there are {} tombstones, but {} items are removed",
+                tombstones, removed
+            ),
+            ValidationError::EmptyLayer { level } => write!(f, "level {} is empty", level),
+            ValidationError::ItemOutOfRange { level, node, item } => write!(
+                f,
+                "node {} on level {} refers to item {}, which does not exist",
+                node, level, item
+            ),
+            ValidationError::DuplicateItem { level, item } => {
+                write!(f, "item {} appears more than once on level {}", item, level)
+            }
+            ValidationError::NextNodeOutOfRange {
+                level,
+                node,
+                next_node,
+            } => write!(
+                f,
+                "node {} on level {} refers to node {} on the level below, which does not exist",
+                node, level, next_node
+            ),
+            ValidationError::NextNodeMismatch { level, node } => write!(
+                f,
+                "node {} on level {} refers to a node on the level below for a different item",
+                node, level
+            ),
+            ValidationError::NeighborOutOfRange {
+                level,
+                node,
+                neighbor,
+            } => write!(
+                f,
+                "node {} on level {} has neighbor {}, which does not exist",
+                node, level, neighbor
+            ),
+            ValidationError::SelfNeighbor { level, node } => {
+                write!(f, "node {} on level {} is its own neighbor", node, level)
+            }
+            ValidationError::DuplicateNeighbor {
+                level,
+                node,
+                neighbor,
+            } => write!(
+                f,
+                "node {} on level {} has neighbor {} more than once",
+                node, level, neighbor
+            ),
+            ValidationError::NeighborAfterEmptySlot { level, node } => write!(
+                f,
+                "node {} on level {} has a neighbor after an empty slot",
+                node, level
+            ),
+            ValidationError::InboundEdges { level, node } => write!(
+                f,
+                "the inbound edges of node {} on level {} do not match the graph",
+                node, level
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+impl<Met, T, R, S, const M: usize, const M0: usize> Hnsw<Met, T, R, M, M0, S> {
+    /// Checks every structural invariant of the graph, which a HNSW built through its methods always upholds.
+    ///
+    /// Searching or inserting into a HNSW which fails this may panic. This is run when a HNSW is deserialized
+    /// or loaded, so it is only needed if the graph came from somewhere else. This visits every node and edge.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let items = self.zero.len();
+        if self.features.len() != items {
+            return Err(ValidationError::FeatureCount {
+                features: self.features.len(),
+                items,
+            });
+        }
+        if self.removed.len() > items {
+            return Err(ValidationError::RemovedCount {
+                removed: self.removed.len(),
+                items,
+            });
+        }
+        let removed = self.removed.iter().filter(|&&removed| removed).count();
+        if self.tombstones != removed {
+            return Err(ValidationError::TombstoneCount {
+                tombstones: self.tombstones,
+                removed,
+            });
+        }
+
+        validate_graph(self)?;
+
+        // Every neighbor is unique, so the inbound edges match if they have every edge and no more.
+        if self.params.inbound_edges {
+            for level in 0..self.layers() {
+                let len = self.layer_len(level);
+                let mut inbound = vec![0usize; len];
+                for node in 0..len {
+                    for neighbor in self.neighbors(level, node) {
+                        inbound[neighbor] += 1;
+                    }
+                }
+                let layer_inbound: Vec<&[usize]> = if level == 0 {
+                    self.zero_inbound.iter().map(|n| &n.neighbors[..]).collect()
+                } else {
+                    self.layers_inbound
+                        .get(level - 1)
+                        .map(|layer| layer.iter().map(|n| &n.neighbors[..]).collect())
+                        .unwrap_or_default()
+                };
+                for (node, &count) in inbound.iter().enumerate() {
+                    let matches = layer_inbound.get(node).is_some_and(|from| {
+                        from.len() == count
+                            && from.iter().all(|&from| {
+                                from < len && self.neighbors_contain(level, from, node)
+                            })
+                    });
+                    if !matches {
+                        return Err(ValidationError::InboundEdges { level, node });
+                    }
+                }
+                if layer_inbound.len() != len {
+                    return Err(ValidationError::InboundEdges { level, node: len });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if a node has a neighbor on a layer, where the node is known to exist.
+    fn neighbors_contain(&self, level: usize, node: usize, neighbor: usize) -> bool {
+        let slots: &[usize] = if level == 0 {
+            &self.zero[node].neighbors
+        } else {
+            &self.layers[level - 1][node].neighbors.neighbors
+        };
+        slots.contains(&neighbor)
+    }
+}
+
+/// Checks the invariants of the layers of a graph that searching it relies on, which are all of those checked by
+/// [`Hnsw::validate`] except for the counts and the inbound edges.
+pub(super) fn validate_graph(graph: &impl Graph) -> Result<(), ValidationError> {
+    let items = graph.layer_len(0);
+    for level in 1..graph.layers() {
+        let len = graph.layer_len(level);
+        if len == 0 {
+            return Err(ValidationError::EmptyLayer { level });
+        }
+        let below = graph.layer_len(level - 1);
+        let mut seen = vec![false; items];
+        for node in 0..len {
+            let item = graph.zero_node(level, node);
+            if item >= items {
+                return Err(ValidationError::ItemOutOfRange { level, node, item });
+            }
+            if core::mem::replace(&mut seen[item], true) {
+                return Err(ValidationError::DuplicateItem { level, item });
+            }
+            let next_node = graph.next_node(level, node);
+            if next_node >= below {
+                return Err(ValidationError::NextNodeOutOfRange {
+                    level,
+                    node,
+                    next_node,
+                });
+            }
+            if graph.zero_node(level - 1, next_node) != item {
+                return Err(ValidationError::NextNodeMismatch { level, node });
+            }
+        }
+    }
+
+    let mut neighbors = Vec::new();
+    for level in 0..graph.layers() {
+        let len = graph.layer_len(level);
+        for node in 0..len {
+            neighbors.clear();
+            neighbors.extend(graph.neighbors(level, node));
+            if graph
+                .slots(level, node)
+                .skip(neighbors.len())
+                .any(|n| n != !0)
+            {
+                return Err(ValidationError::NeighborAfterEmptySlot { level, node });
+            }
+            for (slot, &neighbor) in neighbors.iter().enumerate() {
+                if neighbor >= len {
+                    return Err(ValidationError::NeighborOutOfRange {
+                        level,
+                        node,
+                        neighbor,
+                    });
+                }
+                if neighbor == node {
+                    return Err(ValidationError::SelfNeighbor { level, node });
+                }
+                if neighbors[..slot].contains(&neighbor) {
+                    return Err(ValidationError::DuplicateNeighbor {
+                        level,
+                        node,
+                        neighbor,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The fields of a [`Hnsw`] as they are deserialized, before they are checked with [`Hnsw::validate`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "Met: Deserialize<'de>, T: Deserialize<'de>, R: Deserialize<'de>, S: Deserialize<'de> + Default"
+))]
+pub(super) struct UncheckedHnsw<Met, T, R, const M: usize, const M0: usize, S> {
+    metric: Met,
+    zero: Vec<NeighborNodes<M0>>,
+    features: Vec<T>,
+    layers: Vec<Vec<Node<M>>>,
+    prng: R,
+    params: Params,
+    #[serde(default)]
+    removed: Vec<bool>,
+    #[serde(default)]
+    tombstones: usize,
+    #[serde(default)]
+    zero_inbound: Vec<InboundNodes<M0>>,
+    #[serde(default)]
+    layers_inbound: Vec<Vec<InboundNodes<M>>>,
+    #[serde(default)]
+    selector: S,
+}
+
+#[cfg(feature = "serde")]
+impl<Met, T, R, S, const M: usize, const M0: usize> TryFrom<UncheckedHnsw<Met, T, R, M, M0, S>>
+    for Hnsw<Met, T, R, M, M0, S>
+{
+    type Error = ValidationError;
+
+    fn try_from(unchecked: UncheckedHnsw<Met, T, R, M, M0, S>) -> Result<Self, ValidationError> {
+        let hnsw = Self {
+            metric: unchecked.metric,
+            zero: unchecked.zero,
+            features: unchecked.features,
+            layers: unchecked.layers,
+            prng: unchecked.prng,
+            params: unchecked.params,
+            removed: unchecked.removed,
+            tombstones: unchecked.tombstones,
+            zero_inbound: unchecked.zero_inbound,
+            layers_inbound: unchecked.layers_inbound,
+            selector: unchecked.selector,
+        };
+        hnsw.validate()?;
+        Ok(hnsw)
+    }
+}
@@ -0,0 +1,243 @@
+use crate::*;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::Hash;
+use hashbrown::HashMap;
+use rand_core::RngCore;
+use space::{Metric, Neighbor};
+#[cfg(feature = "serde")]
+use {
+    core::convert::TryFrom,
+    serde::{Deserialize, Serialize},
+};
+
+/// A [`Hnsw`] whose items are identified by keys rather than by their index.
+///
+/// Every key identifies one item, and searches return the keys of the nearest items. The keys stay with their
+/// items when the HNSW is compacted, and they are serialized along with it.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(bound(
+        serialize = "K: Serialize, Met: Serialize, T: Serialize, R: Serialize, S: Serialize",
+        deserialize = "K: Deserialize<'de> + Hash + Eq + Clone, Met: Metric<T> + Deserialize<'de>, T: Deserialize<'de>, R: RngCore + Deserialize<'de>, S: NeighborSelector<Met, T> + Deserialize<'de> + Default"
+    )),
+    serde(try_from = "UncheckedKeyedHnsw<K, Met, T, R, M, M0, S>")
+)]
+pub struct KeyedHnsw<K, Met, T, R, const M: usize, const M0: usize, S = NeighborSelection> {
+    hnsw: Hnsw<Met, T, R, M, M0, S>,
+    /// The key of every item, including the removed items.
+    keys: Vec<K>,
+    /// The item of every key which hasn't been removed.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    items: HashMap<K, usize>,
+}
+
+/// The error returned when an item is inserted into a [`KeyedHnsw`] with a key it already has.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKey<K, T> {
+    /// The key which was already in the HNSW.
+    pub key: K,
+    /// The feature which wasn't inserted.
+    pub feature: T,
+}
+
+impl<K, T> fmt::Display for DuplicateKey<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the key is already in the HNSW")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: fmt::Debug, T: fmt::Debug> std::error::Error for DuplicateKey<K, T> {}
+
+impl<K, Met, T, R, S, const M: usize, const M0: usize> KeyedHnsw<K, Met, T, R, M, M0, S>
+where
+    K: Hash + Eq + Clone,
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Gives keys to the items inserted into an empty HNSW, which decides the metric, params and PRNG.
+    ///
+    /// Panics if the HNSW isn't empty.
+    pub fn new(hnsw: Hnsw<Met, T, R, M, M0, S>) -> Self {
+        assert!(hnsw.is_empty(), "a keyed HNSW must start empty");
+        Self {
+            hnsw,
+            keys: Vec::new(),
+            items: HashMap::new(),
+        }
+    }
+
+    /// Inserts a feature under a key.
+    ///
+    /// If the key is already in the HNSW, nothing is inserted and the key and feature are given back.
+    pub fn insert(
+        &mut self,
+        key: K,
+        q: T,
+        searcher: &mut Searcher<Met::Unit>,
+    ) -> Result<(), DuplicateKey<K, T>> {
+        if self.items.contains_key(&key) {
+            return Err(DuplicateKey { key, feature: q });
+        }
+        let item = self.hnsw.insert(q, searcher);
+        self.keys.push(key.clone());
+        self.items.insert(key, item);
+        Ok(())
+    }
+
+    /// Replaces the feature of the item with a key like [`Hnsw::update`].
+    ///
+    /// Returns `false` if the key isn't in the HNSW.
+    pub fn update<Q>(&mut self, key: &Q, q: T, searcher: &mut Searcher<Met::Unit>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.items.get(key) {
+            Some(&item) => {
+                self.hnsw.update(item, q, searcher);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the item with a key like [`Hnsw::remove`], after which the key can be inserted again.
+    ///
+    /// Returns `false` if the key isn't in the HNSW.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.items.remove(key) {
+            Some(item) => {
+                self.hnsw.remove(item);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Physically deletes the removed items like [`Hnsw::compact`], keeping every key with its item.
+    pub fn compact(&mut self) {
+        let mapping = self.hnsw.compact();
+        let mut item = 0;
+        self.keys.retain(|_| {
+            item += 1;
+            mapping[item - 1] != !0
+        });
+        for (item, key) in self.keys.iter().enumerate() {
+            *self.items.get_mut(key).unwrap() = item;
+        }
+    }
+
+    /// Searches for the nearest neighbors of `q` like [`Hnsw::nearest`], and gives the key of each of them.
+    pub fn nearest<'a>(
+        &'a self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> impl Iterator<Item = Neighbor<Met::Unit, &'a K>> + 'a {
+        let found: &'a [Neighbor<Met::Unit>] = self.hnsw.nearest(q, ef, searcher, dest);
+        found.iter().map(move |n| Neighbor {
+            index: &self.keys[n.index],
+            distance: n.distance,
+        })
+    }
+
+    /// Gets the feature of the item with a key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.items.get(key).map(|&item| self.hnsw.feature(item))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.items.contains_key(key)
+    }
+
+    /// Gets the index in the [`Hnsw`] of the item with a key.
+    pub fn item<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.items.get(key).copied()
+    }
+
+    /// Gets the key of an item in the [`Hnsw`], even if it was removed.
+    pub fn key(&self, item: usize) -> &K {
+        &self.keys[item]
+    }
+
+    /// The number of keys, which doesn't include removed items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Gets the [`Hnsw`] which holds the items, for searches that give indices.
+    pub fn hnsw(&self) -> &Hnsw<Met, T, R, M, M0, S> {
+        &self.hnsw
+    }
+
+    /// Gets the [`Hnsw`] and the key of every item in it.
+    pub fn into_parts(self) -> (Hnsw<Met, T, R, M, M0, S>, Vec<K>) {
+        (self.hnsw, self.keys)
+    }
+}
+
+/// The fields of a [`KeyedHnsw`] as they are deserialized, before the keys are checked.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "K: Deserialize<'de>, Met: Deserialize<'de>, T: Deserialize<'de>, R: Deserialize<'de>, S: Deserialize<'de> + Default"
+))]
+struct UncheckedKeyedHnsw<K, Met, T, R, const M: usize, const M0: usize, S> {
+    hnsw: Hnsw<Met, T, R, M, M0, S>,
+    keys: Vec<K>,
+}
+
+#[cfg(feature = "serde")]
+impl<K, Met, T, R, S, const M: usize, const M0: usize>
+    TryFrom<UncheckedKeyedHnsw<K, Met, T, R, M, M0, S>> for KeyedHnsw<K, Met, T, R, M, M0, S>
+where
+    K: Hash + Eq + Clone,
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Error = &'static str;
+
+    fn try_from(
+        unchecked: UncheckedKeyedHnsw<K, Met, T, R, M, M0, S>,
+    ) -> Result<Self, Self::Error> {
+        let UncheckedKeyedHnsw { hnsw, keys } = unchecked;
+        if keys.len() != hnsw.len() {
+            return Err("the number of keys does not match the number of items");
+        }
+        let mut items = HashMap::with_capacity(keys.len());
+        for (item, key) in keys.iter().enumerate() {
+            if !hnsw.is_removed(item) && items.insert(key.clone(), item).is_some() {
+                return Err("two items have the same key");
+            }
+        }
+        Ok(Self { hnsw, keys, items })
+    }
+}
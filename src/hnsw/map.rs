@@ -0,0 +1,206 @@
+use crate::*;
+use alloc::vec::Vec;
+use rand_core::RngCore;
+use space::{Knn, KnnInsert, KnnMap, KnnPoints, Metric, Neighbor};
+#[cfg(feature = "serde")]
+use {
+    core::convert::TryFrom,
+    serde::{Deserialize, Serialize},
+};
+
+/// A [`Hnsw`] which stores a value with every item, like a [`KnnMap`].
+///
+/// Searches give the value of each neighbor along with it. The values stay with their items when the HNSW is
+/// compacted, and they are serialized along with it.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(bound(
+        serialize = "Met: Serialize, T: Serialize, V: Serialize, R: Serialize, S: Serialize",
+        deserialize = "Met: Metric<T> + Deserialize<'de>, T: Deserialize<'de>, V: Deserialize<'de>, R: RngCore + Deserialize<'de>, S: NeighborSelector<Met, T> + Deserialize<'de> + Default"
+    )),
+    serde(try_from = "UncheckedHnswMap<Met, T, V, R, M, M0, S>")
+)]
+pub struct HnswMap<Met, T, V, R, const M: usize, const M0: usize, S = NeighborSelection> {
+    hnsw: Hnsw<Met, T, R, M, M0, S>,
+    /// The value of every item, including the removed items.
+    values: Vec<V>,
+}
+
+impl<Met, T, V, R, S, const M: usize, const M0: usize> HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    /// Stores values with the items inserted into an empty HNSW, which decides the metric, params and PRNG.
+    ///
+    /// Panics if the HNSW isn't empty.
+    pub fn new(hnsw: Hnsw<Met, T, R, M, M0, S>) -> Self {
+        assert!(hnsw.is_empty(), "a HNSW map must start empty");
+        Self {
+            hnsw,
+            values: Vec::new(),
+        }
+    }
+
+    /// Inserts a feature with its value and returns the item like [`Hnsw::insert`].
+    pub fn insert(&mut self, q: T, value: V, searcher: &mut Searcher<Met::Unit>) -> usize {
+        let item = self.hnsw.insert(q, searcher);
+        self.values.push(value);
+        item
+    }
+
+    /// Replaces the feature of an item like [`Hnsw::update`], keeping its value.
+    pub fn update(&mut self, item: usize, q: T, searcher: &mut Searcher<Met::Unit>) {
+        self.hnsw.update(item, q, searcher);
+    }
+
+    /// Removes an item like [`Hnsw::remove`].
+    ///
+    /// The value is kept until the HNSW is compacted, so it can still be read with [`HnswMap::value`].
+    pub fn remove(&mut self, item: usize) -> bool {
+        self.hnsw.remove(item)
+    }
+
+    /// Physically deletes the removed items and their values like [`Hnsw::compact`].
+    ///
+    /// Returns a mapping from each old item index to its new item index, or `!0` if the item was deleted.
+    pub fn compact(&mut self) -> Vec<usize> {
+        let mapping = self.hnsw.compact();
+        let mut item = 0;
+        self.values.retain(|_| {
+            item += 1;
+            mapping[item - 1] != !0
+        });
+        mapping
+    }
+
+    /// Searches for the nearest neighbors of `q` like [`Hnsw::nearest`], and gives the value of each of them.
+    pub fn nearest<'a>(
+        &'a self,
+        q: &T,
+        ef: usize,
+        searcher: &mut Searcher<Met::Unit>,
+        dest: &'a mut [Neighbor<Met::Unit>],
+    ) -> impl Iterator<Item = (Neighbor<Met::Unit>, &'a V)> + 'a {
+        let found: &'a [Neighbor<Met::Unit>] = self.hnsw.nearest(q, ef, searcher, dest);
+        found.iter().map(move |&n| (n, &self.values[n.index]))
+    }
+
+    /// Gets the value of an item, even if it was removed.
+    pub fn value(&self, item: usize) -> &V {
+        &self.values[item]
+    }
+
+    /// Gets the value of an item mutably, even if it was removed.
+    pub fn value_mut(&mut self, item: usize) -> &mut V {
+        &mut self.values[item]
+    }
+
+    /// Gets the feature of an item.
+    pub fn feature(&self, item: usize) -> &T {
+        self.hnsw.feature(item)
+    }
+
+    /// The number of items, including removed items, like [`Hnsw::len`].
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Gets the [`Hnsw`] which holds the items.
+    pub fn hnsw(&self) -> &Hnsw<Met, T, R, M, M0, S> {
+        &self.hnsw
+    }
+
+    /// Gets the [`Hnsw`] and the value of every item in it.
+    pub fn into_parts(self) -> (Hnsw<Met, T, R, M, M0, S>, Vec<V>) {
+        (self.hnsw, self.values)
+    }
+}
+
+impl<Met, T, V, R, S, const M: usize, const M0: usize> Knn for HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Ix = usize;
+    type Metric = Met;
+    type Point = T;
+    type KnnIter = Vec<Neighbor<Met::Unit>>;
+
+    fn knn(&self, query: &T, num: usize) -> Self::KnnIter {
+        self.hnsw.knn(query, num)
+    }
+}
+
+impl<Met, T, V, R, S, const M: usize, const M0: usize> KnnPoints for HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    fn get_point(&self, index: usize) -> &'_ T {
+        self.hnsw.get_point(index)
+    }
+}
+
+impl<Met, T, V, R, S, const M: usize, const M0: usize> KnnMap for HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Value = V;
+
+    fn get_value(&self, index: usize) -> &'_ V {
+        &self.values[index]
+    }
+}
+
+impl<Met, T, V, R, S, const M: usize, const M0: usize> KnnInsert for HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    fn insert(&mut self, key: T, value: V) -> usize {
+        HnswMap::insert(self, key, value, &mut Searcher::default())
+    }
+}
+
+/// The fields of a [`HnswMap`] as they are deserialized, before the values are checked.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(
+    deserialize = "Met: Deserialize<'de>, T: Deserialize<'de>, V: Deserialize<'de>, R: Deserialize<'de>, S: Deserialize<'de> + Default"
+))]
+struct UncheckedHnswMap<Met, T, V, R, const M: usize, const M0: usize, S> {
+    hnsw: Hnsw<Met, T, R, M, M0, S>,
+    values: Vec<V>,
+}
+
+#[cfg(feature = "serde")]
+impl<Met, T, V, R, S, const M: usize, const M0: usize>
+    TryFrom<UncheckedHnswMap<Met, T, V, R, M, M0, S>> for HnswMap<Met, T, V, R, M, M0, S>
+where
+    R: RngCore,
+    Met: Metric<T>,
+    S: NeighborSelector<Met, T>,
+{
+    type Error = &'static str;
+
+    fn try_from(unchecked: UncheckedHnswMap<Met, T, V, R, M, M0, S>) -> Result<Self, Self::Error> {
+        let UncheckedHnswMap { hnsw, values } = unchecked;
+        if values.len() != hnsw.len() {
+            return Err("the number of values does not match the number of items");
+        }
+        Ok(Self { hnsw, values })
+    }
+}
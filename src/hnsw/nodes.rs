@@ -1,23 +1,7 @@
-use core::{
-    iter::{Cloned, TakeWhile},
-    slice::Iter,
-};
-
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
-pub enum Layer<T> {
-    Zero,
-    NonZero(T),
-}
-
-pub trait HasNeighbors<'a, 'b> {
-    type NeighborIter: Iterator<Item = usize> + 'a;
-
-    fn get_neighbors(&'b self) -> Self::NeighborIter;
-}
-
 /// A node in the zero layer
 #[derive(Clone, Debug)]
 pub struct NeighborNodes<const N: usize> {
@@ -25,14 +9,6 @@ pub struct NeighborNodes<const N: usize> {
     pub neighbors: [usize; N],
 }
 
-impl<'a, 'b: 'a, const N: usize> HasNeighbors<'a, 'b> for NeighborNodes<N> {
-    type NeighborIter = TakeWhile<Cloned<Iter<'a, usize>>, fn(&usize) -> bool>;
-
-    fn get_neighbors(&'b self) -> Self::NeighborIter {
-        self.neighbors.iter().cloned().take_while(|&n| n != !0)
-    }
-}
-
 /// A node in any other layer other than the zero layer
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(bound = ""))]
@@ -45,16 +21,8 @@ pub struct Node<const N: usize> {
     pub neighbors: NeighborNodes<N>,
 }
 
-impl<'a, 'b: 'a, const N: usize> HasNeighbors<'a, 'b> for Node<N> {
-    type NeighborIter = TakeWhile<Cloned<Iter<'a, usize>>, fn(&usize) -> bool>;
-
-    fn get_neighbors(&'b self) -> Self::NeighborIter {
-        self.neighbors.get_neighbors()
-    }
-}
-
 /// The inbound nodes that are pointing to this node.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(bound = ""))]
 pub struct InboundNodes<const N: usize> {
     pub neighbors: SmallVec<[usize; N]>,
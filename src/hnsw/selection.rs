@@ -0,0 +1,343 @@
+use ahash::RandomState;
+use alloc::{vec, vec::Vec};
+use core::ops::Deref;
+use hashbrown::HashSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use space::{Metric, Neighbor};
+
+/// A view of a single layer of the graph, given to a [`NeighborSelector`] while it chooses neighbors.
+///
+/// Nodes are identified by their unique index on the layer.
+pub trait LayerView<T> {
+    /// Gets the feature of a node.
+    fn feature(&self, node: usize) -> &T;
+
+    /// The current neighbors of a node.
+    type Neighbors<'a>: Deref<Target = [usize]>
+    where
+        Self: 'a;
+
+    /// Gets the current neighbors of a node.
+    fn neighbors(&self, node: usize) -> Self::Neighbors<'_>;
+
+    /// Checks if a node is an item that was removed and will be deleted when the graph is compacted.
+    fn is_removed(&self, node: usize) -> bool;
+}
+
+/// Decides which edges are kept in the graph.
+///
+/// This is consulted whenever a node is linked into the graph, and also when the graph is repaired during
+/// [`Hnsw::update`](crate::Hnsw::update) and [`Hnsw::compact`](crate::Hnsw::compact).
+pub trait NeighborSelector<Met, T>
+where
+    Met: Metric<T>,
+{
+    /// Chooses at most `max_neighbors` of the `candidates` to become the neighbors of `node`.
+    ///
+    /// The `candidates` are sorted by their distance from `node` and never contain `node`.
+    fn select_neighbors<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        candidates: &[Neighbor<Met::Unit>],
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>>;
+
+    /// Decides if `new_neighbor` can simply take a free neighbor slot of `node` when it is linked to it,
+    /// without choosing the neighbors of `node` again. This is only asked if `node` has fewer than
+    /// `max_neighbors` neighbors, and [`NeighborSelector::replace_neighbors`] is used otherwise.
+    ///
+    /// By default, this returns `false`, so that every edge is chosen by the selector, which lets it cap the
+    /// number of neighbors below `max_neighbors` or prune them whenever one is added.
+    fn append_neighbor<G: LayerView<T>>(
+        &self,
+        _metric: &Met,
+        _graph: &G,
+        _node: usize,
+        _new_neighbor: Neighbor<Met::Unit>,
+        _max_neighbors: usize,
+    ) -> bool {
+        false
+    }
+
+    /// Chooses at most `max_neighbors` neighbors of `node` when `new_neighbor` is linked to it, but it wasn't
+    /// added to a free slot with [`NeighborSelector::append_neighbor`]. The current `neighbors` of `node` are in
+    /// the order of their slots.
+    ///
+    /// By default, this chooses from all of them with [`NeighborSelector::select_neighbors`].
+    fn replace_neighbors<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        neighbors: &[Neighbor<Met::Unit>],
+        new_neighbor: Neighbor<Met::Unit>,
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        let mut candidates = neighbors.to_vec();
+        candidates.push(new_neighbor);
+        candidates.sort_by_key(|n| n.distance);
+        self.select_neighbors(metric, graph, node, &candidates, max_neighbors)
+    }
+}
+
+/// Keeps the closest candidates. This is Algorithm 3 in the paper.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelectClosest;
+
+impl<Met, T> NeighborSelector<Met, T> for SelectClosest
+where
+    Met: Metric<T>,
+{
+    fn select_neighbors<G: LayerView<T>>(
+        &self,
+        _: &Met,
+        _: &G,
+        _: usize,
+        candidates: &[Neighbor<Met::Unit>],
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        candidates.iter().take(max_neighbors).copied().collect()
+    }
+
+    /// Every new neighbor is kept while there is room for it.
+    fn append_neighbor<G: LayerView<T>>(
+        &self,
+        _: &Met,
+        _: &G,
+        _: usize,
+        _: Neighbor<Met::Unit>,
+        _: usize,
+    ) -> bool {
+        true
+    }
+
+    fn replace_neighbors<G: LayerView<T>>(
+        &self,
+        _: &Met,
+        _: &G,
+        _: usize,
+        neighbors: &[Neighbor<Met::Unit>],
+        new_neighbor: Neighbor<Met::Unit>,
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        let mut neighbors = neighbors.to_vec();
+        if neighbors.len() < max_neighbors {
+            neighbors.push(new_neighbor);
+            return neighbors;
+        }
+        // Find the worst neighbor currently.
+        // This was done instead of max_by_key because min_by_key takes the first equally bad element.
+        if let Some(worst) = neighbors
+            .iter_mut()
+            .min_by_key(|n| core::cmp::Reverse(n.distance))
+        {
+            // If this is better than the worst, insert it in the worst's place.
+            if new_neighbor.distance < worst.distance {
+                *worst = new_neighbor;
+            }
+        }
+        neighbors
+    }
+}
+
+/// Only keeps a candidate if it is closer to the node than to any neighbor already kept, which keeps
+/// neighbors in diverse directions. This is Algorithm 4 in the paper.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelectHeuristic {
+    /// Also consider the neighbors of the candidates as candidates (`extendCandidates` in the paper).
+    /// This is only helpful for extremely clustered data.
+    pub extend_candidates: bool,
+    /// Fill the remaining neighbor slots with the closest discarded candidates
+    /// (`keepPrunedConnections` in the paper).
+    pub keep_pruned_connections: bool,
+}
+
+impl<Met, T> NeighborSelector<Met, T> for SelectHeuristic
+where
+    Met: Metric<T>,
+{
+    fn select_neighbors<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        candidates: &[Neighbor<Met::Unit>],
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        let mut extended;
+        let candidates = if self.extend_candidates {
+            // Add the neighbors of every candidate that aren't already candidates.
+            let mut seen: HashSet<usize, RandomState> =
+                HashSet::with_hasher(RandomState::with_seeds(0, 0, 0, 0));
+            seen.insert(node);
+            seen.extend(candidates.iter().map(|n| n.index));
+            extended = candidates.to_vec();
+            for candidate in candidates {
+                for &neighbor in graph.neighbors(candidate.index).iter() {
+                    // Never extend the candidates to removed items since they will be deleted eventually.
+                    if !graph.is_removed(neighbor) && seen.insert(neighbor) {
+                        extended.push(Neighbor {
+                            index: neighbor,
+                            distance: metric.distance(graph.feature(node), graph.feature(neighbor)),
+                        });
+                    }
+                }
+            }
+            extended.sort_by_key(|n| n.distance);
+            &extended[..]
+        } else {
+            candidates
+        };
+
+        let mut selected: Vec<Neighbor<Met::Unit>> = vec![];
+        let mut discarded = vec![];
+        for &candidate in candidates {
+            if selected.len() == max_neighbors {
+                break;
+            }
+            // Only keep the candidate if it is closer to the node than to any neighbor kept so far.
+            let candidate_feature = graph.feature(candidate.index);
+            if selected.iter().all(|n| {
+                candidate.distance < metric.distance(candidate_feature, graph.feature(n.index))
+            }) {
+                selected.push(candidate);
+            } else if self.keep_pruned_connections {
+                discarded.push(candidate);
+            }
+        }
+
+        // Fill the remaining slots with the closest discarded candidates.
+        if self.keep_pruned_connections {
+            let remaining = max_neighbors - selected.len();
+            selected.extend(discarded.into_iter().take(remaining));
+        }
+        selected
+    }
+
+    /// Like the paper, the neighbors are only chosen again once there is no room for a new neighbor.
+    fn append_neighbor<G: LayerView<T>>(
+        &self,
+        _: &Met,
+        _: &G,
+        _: usize,
+        _: Neighbor<Met::Unit>,
+        _: usize,
+    ) -> bool {
+        true
+    }
+}
+
+/// The built-in methods used to choose the neighbors of a node from its candidates.
+///
+/// This is chosen with [`Params::neighbor_selection`](crate::Params::neighbor_selection). To use a different
+/// method, implement [`NeighborSelector`] and pass it to [`Hnsw::with_selector`](crate::Hnsw::with_selector).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NeighborSelection {
+    /// Keeps the closest candidates. This is Algorithm 3 in the paper. See [`SelectClosest`].
+    #[default]
+    Simple,
+    /// Only keeps a candidate if it is closer to the node than to any neighbor already kept, which keeps
+    /// neighbors in diverse directions. This is Algorithm 4 in the paper, and it greatly improves the
+    /// connectivity of clustered data. See [`SelectHeuristic`].
+    Heuristic {
+        /// Also consider the neighbors of the candidates as candidates (`extendCandidates` in the paper).
+        /// This is only helpful for extremely clustered data.
+        extend_candidates: bool,
+        /// Fill the remaining neighbor slots with the closest discarded candidates
+        /// (`keepPrunedConnections` in the paper).
+        keep_pruned_connections: bool,
+    },
+}
+
+impl<Met, T> NeighborSelector<Met, T> for NeighborSelection
+where
+    Met: Metric<T>,
+{
+    fn select_neighbors<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        candidates: &[Neighbor<Met::Unit>],
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        match *self {
+            NeighborSelection::Simple => {
+                SelectClosest.select_neighbors(metric, graph, node, candidates, max_neighbors)
+            }
+            NeighborSelection::Heuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            } => SelectHeuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            }
+            .select_neighbors(metric, graph, node, candidates, max_neighbors),
+        }
+    }
+
+    fn append_neighbor<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        new_neighbor: Neighbor<Met::Unit>,
+        max_neighbors: usize,
+    ) -> bool {
+        match *self {
+            NeighborSelection::Simple => {
+                SelectClosest.append_neighbor(metric, graph, node, new_neighbor, max_neighbors)
+            }
+            NeighborSelection::Heuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            } => SelectHeuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            }
+            .append_neighbor(metric, graph, node, new_neighbor, max_neighbors),
+        }
+    }
+
+    fn replace_neighbors<G: LayerView<T>>(
+        &self,
+        metric: &Met,
+        graph: &G,
+        node: usize,
+        neighbors: &[Neighbor<Met::Unit>],
+        new_neighbor: Neighbor<Met::Unit>,
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<Met::Unit>> {
+        match *self {
+            NeighborSelection::Simple => SelectClosest.replace_neighbors(
+                metric,
+                graph,
+                node,
+                neighbors,
+                new_neighbor,
+                max_neighbors,
+            ),
+            NeighborSelection::Heuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            } => SelectHeuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            }
+            .replace_neighbors(
+                metric,
+                graph,
+                node,
+                neighbors,
+                new_neighbor,
+                max_neighbors,
+            ),
+        }
+    }
+}
@@ -1,9 +1,24 @@
 #![no_std]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+#[cfg(feature = "std")]
+pub mod eval;
+mod flat;
 mod hnsw;
+pub mod metrics;
+mod mips;
+mod ordered;
+mod pq;
+mod sq;
 
+pub use self::flat::*;
 pub use self::hnsw::*;
+pub use self::mips::*;
+pub use self::ordered::*;
+pub use self::pq::*;
+pub use self::sq::*;
 
 use ahash::RandomState;
 use alloc::{vec, vec::Vec};
@@ -17,6 +32,10 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Params {
     ef_construction: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    inbound_edges: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    neighbor_selection: NeighborSelection,
 }
 
 impl Params {
@@ -36,12 +55,34 @@ impl Params {
         self.ef_construction = ef_construction;
         self
     }
+
+    /// Keeps track of the inbound edges of every node in the graph, allowing [`Hnsw::inbound`] to find
+    /// all nodes pointing to a node without scanning the whole graph. This makes [`Hnsw::compact`] cheaper
+    /// when few items are removed, but costs memory and insertion speed.
+    ///
+    /// Defaults to `false`.
+    pub fn inbound_edges(mut self, inbound_edges: bool) -> Self {
+        self.inbound_edges = inbound_edges;
+        self
+    }
+
+    /// Chooses how the neighbors of each node are selected from the candidates found while inserting.
+    /// See [`NeighborSelection`] for the options. This is ignored if the HNSW was given a different
+    /// [`NeighborSelector`] with [`Hnsw::with_selector`].
+    ///
+    /// Defaults to [`NeighborSelection::Simple`].
+    pub fn neighbor_selection(mut self, neighbor_selection: NeighborSelection) -> Self {
+        self.neighbor_selection = neighbor_selection;
+        self
+    }
 }
 
 impl Default for Params {
     fn default() -> Self {
         Self {
             ef_construction: 400,
+            inbound_edges: false,
+            neighbor_selection: NeighborSelection::Simple,
         }
     }
 }
@@ -59,6 +100,11 @@ impl<Metric> Searcher<Metric> {
         Default::default()
     }
 
+    /// The number of distances computed by the last search of a HNSW with this searcher.
+    pub fn distances(&self) -> usize {
+        self.seen.len()
+    }
+
     fn clear(&mut self) {
         self.candidates.clear();
         self.nearest.clear();
@@ -0,0 +1,265 @@
+//! Common metrics to use with a [`Hnsw`](crate::Hnsw).
+//!
+//! The float metrics are implemented for `&[f32]`, `Vec<f32>` and `[f32; N]`, and [`Hamming`] for the same
+//! containers of `u8`. Every unit sorts in the same order as the distance it stands for. With the `std`
+//! feature, the float metrics use AVX2 on x86 CPUs which support it, and otherwise the code is written so
+//! that it can be vectorized with the SIMD instructions every target has, like SSE2 and NEON. Every path
+//! adds up the numbers in the same order, so the distances are the same on every CPU.
+//!
+//! The float metrics give an [`OrderedF32`], and panic if the distance is NaN, such as when a vector contains NaN.
+
+use crate::OrderedF32;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use space::Metric;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The euclidean distance.
+///
+/// # Panics
+///
+/// Panics if a vector contains NaN, or an infinity where the other vector has the same infinity.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Euclidean;
+
+/// The squared euclidean distance.
+///
+/// This is faster than [`Euclidean`] and finds the same neighbors.
+///
+/// # Panics
+///
+/// Panics like [`Euclidean`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SquaredEuclidean;
+
+/// The negative inner product, so that the vectors with the greatest inner product are the nearest.
+///
+/// Use [`InnerProduct::inner_product`] to get the inner product back. This isn't a true metric, so a HNSW with
+/// it will find the nearest neighbors less reliably than with the other metrics.
+///
+/// # Panics
+///
+/// Panics if the inner product is NaN, such as when a vector contains NaN or when infinite products cancel out.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InnerProduct;
+
+/// The cosine distance, which is one minus the cosine of the angle between the vectors, between `0` and `2`.
+///
+/// A zero vector has a distance of `1` to every vector.
+///
+/// # Panics
+///
+/// Panics if a vector contains NaN or infinity.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cosine;
+
+/// The hamming distance, which is the number of bits that differ.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Hamming;
+
+impl Euclidean {
+    fn f32_distance(a: &[f32], b: &[f32]) -> OrderedF32 {
+        OrderedF32::new(libm::sqrtf(squared_l2(a, b)))
+    }
+}
+
+impl SquaredEuclidean {
+    fn f32_distance(a: &[f32], b: &[f32]) -> OrderedF32 {
+        OrderedF32::new(squared_l2(a, b))
+    }
+}
+
+impl InnerProduct {
+    /// Gets the inner product back from a distance.
+    pub fn inner_product(distance: OrderedF32) -> f32 {
+        -distance.get()
+    }
+
+    fn f32_distance(a: &[f32], b: &[f32]) -> OrderedF32 {
+        OrderedF32::new(-dot(a, b))
+    }
+}
+
+impl Cosine {
+    fn f32_distance(a: &[f32], b: &[f32]) -> OrderedF32 {
+        let [dot, norm_a, norm_b] = cosine_parts(a, b);
+        let norms = libm::sqrtf(norm_a * norm_b);
+        let distance = if norms == 0.0 {
+            1.0
+        } else {
+            (1.0 - dot / norms).clamp(0.0, 2.0)
+        };
+        OrderedF32::new(distance)
+    }
+}
+
+impl Hamming {
+    fn u8_distance(a: &[u8], b: &[u8]) -> u32 {
+        debug_assert_eq!(a.len(), b.len());
+        let chunks_a = a.chunks_exact(8);
+        let chunks_b = b.chunks_exact(8);
+        let remainder = chunks_a
+            .remainder()
+            .iter()
+            .zip(chunks_b.remainder())
+            .map(|(&a, &b)| (a ^ b).count_ones())
+            .sum::<u32>();
+        chunks_a
+            .zip(chunks_b)
+            .map(|(a, b)| {
+                let a = u64::from_le_bytes(a.try_into().unwrap());
+                let b = u64::from_le_bytes(b.try_into().unwrap());
+                (a ^ b).count_ones()
+            })
+            .sum::<u32>()
+            + remainder
+    }
+}
+
+macro_rules! impl_metric {
+    ($metric:ty, $element:ty, $distance:ident, $unit:ty) => {
+        impl<'a> Metric<&'a [$element]> for $metric {
+            type Unit = $unit;
+
+            fn distance(&self, a: &&'a [$element], b: &&'a [$element]) -> $unit {
+                Self::$distance(a, b)
+            }
+        }
+
+        impl Metric<Vec<$element>> for $metric {
+            type Unit = $unit;
+
+            fn distance(&self, a: &Vec<$element>, b: &Vec<$element>) -> $unit {
+                Self::$distance(a, b)
+            }
+        }
+
+        impl<const N: usize> Metric<[$element; N]> for $metric {
+            type Unit = $unit;
+
+            fn distance(&self, a: &[$element; N], b: &[$element; N]) -> $unit {
+                Self::$distance(a, b)
+            }
+        }
+    };
+}
+
+impl_metric!(Euclidean, f32, f32_distance, OrderedF32);
+impl_metric!(SquaredEuclidean, f32, f32_distance, OrderedF32);
+impl_metric!(InnerProduct, f32, f32_distance, OrderedF32);
+impl_metric!(Cosine, f32, f32_distance, OrderedF32);
+impl_metric!(Hamming, u8, u8_distance, u32);
+
+/// Makes a kernel over two vectors which uses AVX2 when the CPU supports it, and otherwise the portable kernel.
+///
+/// The portable kernel must be `#[inline(always)]` so that it is compiled again with AVX2.
+macro_rules! dispatch {
+    ($name:ident = $portable:ident -> $ret:ty) => {
+        fn $name(a: &[f32], b: &[f32]) -> $ret {
+            debug_assert_eq!(a.len(), b.len());
+            #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+            {
+                #[target_feature(enable = "avx2")]
+                unsafe fn avx2(a: &[f32], b: &[f32]) -> $ret {
+                    $portable(a, b)
+                }
+
+                if std::is_x86_feature_detected!("avx2") {
+                    // SAFETY: The CPU supports AVX2.
+                    return unsafe { avx2(a, b) };
+                }
+            }
+            $portable(a, b)
+        }
+    };
+}
+
+dispatch!(squared_l2 = squared_l2_portable -> f32);
+dispatch!(dot = dot_portable -> f32);
+dispatch!(cosine_parts = cosine_parts_portable -> [f32; 3]);
+
+/// The number of separate sums kept by the kernels, which lets them be vectorized without reordering floats.
+const LANES: usize = 8;
+
+#[inline(always)]
+fn squared_l2_portable(a: &[f32], b: &[f32]) -> f32 {
+    let mut sums = [0.0f32; LANES];
+    let chunks_a = a.chunks_exact(LANES);
+    let chunks_b = b.chunks_exact(LANES);
+    for (lane, (&a, &b)) in chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .enumerate()
+    {
+        sums[lane] += (a - b) * (a - b);
+    }
+    for (a, b) in chunks_a.zip(chunks_b) {
+        for lane in 0..LANES {
+            sums[lane] += (a[lane] - b[lane]) * (a[lane] - b[lane]);
+        }
+    }
+    sum_lanes(sums)
+}
+
+#[inline(always)]
+fn dot_portable(a: &[f32], b: &[f32]) -> f32 {
+    let mut sums = [0.0f32; LANES];
+    let chunks_a = a.chunks_exact(LANES);
+    let chunks_b = b.chunks_exact(LANES);
+    for (lane, (&a, &b)) in chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .enumerate()
+    {
+        sums[lane] += a * b;
+    }
+    for (a, b) in chunks_a.zip(chunks_b) {
+        for lane in 0..LANES {
+            sums[lane] += a[lane] * b[lane];
+        }
+    }
+    sum_lanes(sums)
+}
+
+/// Finds the inner product and the squared norms of both vectors.
+#[inline(always)]
+fn cosine_parts_portable(a: &[f32], b: &[f32]) -> [f32; 3] {
+    let mut dots = [0.0f32; LANES];
+    let mut norms_a = [0.0f32; LANES];
+    let mut norms_b = [0.0f32; LANES];
+    let chunks_a = a.chunks_exact(LANES);
+    let chunks_b = b.chunks_exact(LANES);
+    for (lane, (&a, &b)) in chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .enumerate()
+    {
+        dots[lane] += a * b;
+        norms_a[lane] += a * a;
+        norms_b[lane] += b * b;
+    }
+    for (a, b) in chunks_a.zip(chunks_b) {
+        for lane in 0..LANES {
+            dots[lane] += a[lane] * b[lane];
+            norms_a[lane] += a[lane] * a[lane];
+            norms_b[lane] += b[lane] * b[lane];
+        }
+    }
+    [sum_lanes(dots), sum_lanes(norms_a), sum_lanes(norms_b)]
+}
+
+/// Adds up the lanes in pairs, which keeps the additions independent like the lanes.
+#[inline(always)]
+fn sum_lanes([a, b, c, d, e, f, g, h]: [f32; LANES]) -> f32 {
+    ((a + b) + (c + d)) + ((e + f) + (g + h))
+}
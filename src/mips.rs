@@ -0,0 +1,87 @@
+use crate::OrderedF32;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Turns maximum inner product search into nearest neighbor search, so a [`Hnsw`](crate::Hnsw) can find the
+/// items with the greatest inner product with a query without normalizing them.
+///
+/// The inner product isn't a metric, so every item gets one more dimension which brings its norm up to the
+/// largest norm of any item, and every query gets a zero in that dimension. The squared euclidean distance
+/// between them is then the squared norm of the query plus the squared largest norm minus twice their inner
+/// product, so the nearest items have the greatest inner product. Insert the vectors made by
+/// [`MipsTransform::item`] into a HNSW with the [`SquaredEuclidean`](crate::metrics::SquaredEuclidean) or
+/// [`Euclidean`](crate::metrics::Euclidean) metric, and search with the vectors made by
+/// [`MipsTransform::query`].
+///
+/// The items with the largest norms are the ones which are found most often, but they are far from the other
+/// items, so use [`NeighborSelection::Heuristic`](crate::NeighborSelection::Heuristic) to keep them connected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MipsTransform {
+    max_norm: f32,
+}
+
+impl MipsTransform {
+    /// Makes a transform for items with at most the given norm.
+    ///
+    /// Panics if the norm is negative.
+    pub fn new(max_norm: f32) -> Self {
+        assert!(max_norm >= 0.0, "the max norm can't be negative");
+        Self { max_norm }
+    }
+
+    /// Makes a transform for items with at most the largest norm of the given items.
+    ///
+    /// Since the largest norm can't change after items are inserted, this should be given every item, or a
+    /// sample with some room left with [`MipsTransform::new`].
+    pub fn fit<P: AsRef<[f32]>>(items: &[P]) -> Self {
+        let max_squared = items
+            .iter()
+            .map(|v| squared_norm(v.as_ref()))
+            .fold(0.0, f32::max);
+        Self::new(libm::sqrtf(max_squared))
+    }
+
+    /// The largest norm an item can have.
+    pub fn max_norm(&self) -> f32 {
+        self.max_norm
+    }
+
+    /// Adds the dimension which brings the norm of an item up to the max norm.
+    ///
+    /// Panics if the item has a norm greater than the max norm, beyond rounding errors.
+    pub fn item(&self, v: &[f32]) -> Vec<f32> {
+        let max_squared = self.max_norm * self.max_norm;
+        let remaining = max_squared - squared_norm(v);
+        assert!(
+            remaining >= -1e-5 * max_squared,
+            "the item has a norm greater than the max norm"
+        );
+        let mut item = Vec::with_capacity(v.len() + 1);
+        item.extend_from_slice(v);
+        item.push(libm::sqrtf(remaining.max(0.0)));
+        item
+    }
+
+    /// Adds a zero to a query, which keeps its inner product with the items.
+    pub fn query(&self, v: &[f32]) -> Vec<f32> {
+        let mut query = Vec::with_capacity(v.len() + 1);
+        query.extend_from_slice(v);
+        query.push(0.0);
+        query
+    }
+
+    /// Gets the inner product of a query and an item back from the squared euclidean distance between them,
+    /// as given by [`SquaredEuclidean`](crate::metrics::SquaredEuclidean).
+    ///
+    /// The `query` is the query before it was given to [`MipsTransform::query`].
+    pub fn inner_product(&self, query: &[f32], distance: OrderedF32) -> f32 {
+        (squared_norm(query) + self.max_norm * self.max_norm - distance.get()) / 2.0
+    }
+}
+
+fn squared_norm(v: &[f32]) -> f32 {
+    v.iter().map(|&x| x * x).sum()
+}
@@ -0,0 +1,194 @@
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Add, Div, Mul, Rem, Sub};
+use num_traits::{Num, NumCast, One, ToPrimitive, Unsigned, Zero};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The error when a NaN is given where an ordered float is needed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NanError;
+
+impl fmt::Display for NanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "an ordered float can't be NaN")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NanError {}
+
+macro_rules! ordered_float {
+    ($(#[$attr:meta])* $name:ident, $float:ty, $float_name:literal) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Default, PartialEq)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(Serialize, Deserialize),
+            serde(try_from = $float_name, into = $float_name)
+        )]
+        pub struct $name($float);
+
+        impl $name {
+            /// Wraps a float.
+            ///
+            /// Panics if it is NaN.
+            pub fn new(x: $float) -> Self {
+                Self::try_new(x).expect("an ordered float can't be NaN")
+            }
+
+            /// Wraps a float, or gives `None` if it is NaN.
+            pub fn try_new(x: $float) -> Option<Self> {
+                if x.is_nan() {
+                    None
+                } else {
+                    Some(Self(x))
+                }
+            }
+
+            /// Gets the float.
+            pub fn get(self) -> $float {
+                self.0
+            }
+        }
+
+        // There are no NaNs, so the partial order is total.
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.partial_cmp(&other.0).unwrap()
+            }
+        }
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                // Zero and negative zero are equal, so they must hash the same.
+                let x = if self.0 == 0.0 { 0.0 } else { self.0 };
+                x.to_bits().hash(state);
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl TryFrom<$float> for $name {
+            type Error = NanError;
+
+            fn try_from(x: $float) -> Result<Self, NanError> {
+                Self::try_new(x).ok_or(NanError)
+            }
+        }
+
+        impl From<$name> for $float {
+            fn from(x: $name) -> $float {
+                x.0
+            }
+        }
+
+        ordered_float!(@op $name, Add, add);
+        ordered_float!(@op $name, Sub, sub);
+        ordered_float!(@op $name, Mul, mul);
+        ordered_float!(@op $name, Div, div);
+        ordered_float!(@op $name, Rem, rem);
+
+        impl Zero for $name {
+            fn zero() -> Self {
+                Self(0.0)
+            }
+
+            fn is_zero(&self) -> bool {
+                self.0 == 0.0
+            }
+        }
+
+        impl One for $name {
+            fn one() -> Self {
+                Self(1.0)
+            }
+        }
+
+        impl Num for $name {
+            type FromStrRadixErr = <$float as Num>::FromStrRadixErr;
+
+            fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+                <$float>::from_str_radix(s, radix).map(Self::new)
+            }
+        }
+
+        // This is a lie which `space` forces, since it requires every distance unit to be `Unsigned`. The HNSW only
+        // compares distances and starts from zero, so nothing in this crate relies on it.
+        impl Unsigned for $name {}
+
+        impl ToPrimitive for $name {
+            fn to_i64(&self) -> Option<i64> {
+                self.0.to_i64()
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                self.0.to_u64()
+            }
+
+            fn to_f64(&self) -> Option<f64> {
+                self.0.to_f64()
+            }
+        }
+
+        impl NumCast for $name {
+            fn from<N: ToPrimitive>(n: N) -> Option<Self> {
+                <$float as NumCast>::from(n).and_then(Self::try_new)
+            }
+        }
+    };
+    (@op $name:ident, $op:ident, $method:ident) => {
+        impl $op for $name {
+            type Output = Self;
+
+            /// Panics if the result is NaN.
+            fn $method(self, other: Self) -> Self {
+                Self::new(self.0.$method(other.0))
+            }
+        }
+    };
+}
+
+ordered_float!(
+    /// An `f32` which can't be NaN, so it is totally ordered and can be the distance unit of a
+    /// [`Metric`](space::Metric).
+    ///
+    /// Unlike the bits of an `f32`, this sorts negative distances correctly, such as the negative inner product.
+    /// Zero and negative zero are equal. Arithmetic which results in NaN panics.
+    ///
+    /// # Warning
+    ///
+    /// This implements [`Unsigned`] only because `space` requires it of every distance unit, but it can be
+    /// negative. Generic code outside of this crate which relies on `Unsigned` values being non-negative, such as
+    /// by subtracting distances and assuming the result is no greater than either of them, is wrong for it.
+    OrderedF32,
+    f32,
+    "f32"
+);
+
+ordered_float!(
+    /// An `f64` which can't be NaN, like [`OrderedF32`].
+    ///
+    /// # Warning
+    ///
+    /// This implements [`Unsigned`] only because `space` requires it of every distance unit, but it can be
+    /// negative, like [`OrderedF32`].
+    OrderedF64,
+    f64,
+    "f64"
+);
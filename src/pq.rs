@@ -0,0 +1,293 @@
+use crate::OrderedF32;
+use alloc::{boxed::Box, vec, vec::Vec};
+use rand_core::RngCore;
+use space::{Metric, Neighbor};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A product quantization codec, which compresses `f32` vectors to one byte for each subspace.
+///
+/// Every vector is split into subspaces of equal dimension, and each part is replaced by the index of the
+/// closest of the centroids learned for that subspace by [`PqCodec::train`]. A 768-dimensional vector split
+/// into 96 subspaces takes 96 bytes instead of 3072.
+///
+/// The codec is the [`Metric`] of a [`Hnsw`](crate::Hnsw) over [`PqFeature`]. Insert the features made by
+/// [`PqCodec::encode`], and search with the features made by [`PqCodec::query`], which keep the query in
+/// full precision as a table of its distance to every centroid. The distance is the squared euclidean
+/// distance as an [`OrderedF32`]. Use [`rerank`] to correct the order of the nearest neighbors with their
+/// full-precision vectors.
+///
+/// The distance panics if it is NaN, which happens when the vectors or the sample contain NaN.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PqCodec {
+    dim: usize,
+    subspaces: usize,
+    centroids: usize,
+    /// The centroids of every subspace, one subspace after another.
+    codebooks: Vec<f32>,
+    /// The squared distance between every pair of centroids in every subspace.
+    centroid_distances: Vec<f32>,
+}
+
+/// A feature compressed by a [`PqCodec`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PqFeature {
+    /// The centroid of every subspace, made by [`PqCodec::encode`], which is what a HNSW stores.
+    Codes(Box<[u8]>),
+    /// A vector made by [`PqCodec::query`] to search with.
+    Query {
+        /// The full-precision vector, which is what two queries are compared with.
+        vector: Box<[f32]>,
+        /// The squared distance to every centroid of every subspace, which is what codes are compared with.
+        table: Box<[f32]>,
+    },
+}
+
+impl PqFeature {
+    /// Gets the codes if this was made by [`PqCodec::encode`].
+    pub fn codes(&self) -> Option<&[u8]> {
+        match self {
+            PqFeature::Codes(codes) => Some(codes),
+            PqFeature::Query { .. } => None,
+        }
+    }
+}
+
+impl PqCodec {
+    /// Learns the centroids of every subspace from a sample of vectors with k-means.
+    ///
+    /// The sample should be representative of the vectors which will be encoded, and must have at least as many
+    /// vectors as `centroids`. The centroids start at random vectors of the sample, and are refined for at most
+    /// `iterations` rounds, stopping early once no vector changes centroid.
+    ///
+    /// Panics if the sample is too small, if the vectors are empty or don't all have a dimension divisible by
+    /// `subspaces`, or if `centroids` isn't between `1` and `256`.
+    pub fn train<P: AsRef<[f32]>>(
+        sample: &[P],
+        subspaces: usize,
+        centroids: usize,
+        iterations: usize,
+        prng: &mut impl RngCore,
+    ) -> Self {
+        assert!(
+            (1..=256).contains(&centroids),
+            "there must be between 1 and 256 centroids"
+        );
+        assert!(
+            sample.len() >= centroids,
+            "the sample must have at least as many vectors as centroids"
+        );
+        let dim = sample[0].as_ref().len();
+        assert!(
+            sample.iter().all(|v| v.as_ref().len() == dim),
+            "the sample vectors must have the same dimension"
+        );
+        assert!(dim != 0, "the sample vectors must not be empty");
+        assert!(
+            subspaces != 0 && dim % subspaces == 0,
+            "the dimension must be divisible by the number of subspaces"
+        );
+        let sub_dim = dim / subspaces;
+
+        // Every subspace starts with the same random vectors of the sample.
+        let mut order: Vec<usize> = (0..sample.len()).collect();
+        for i in 0..centroids {
+            let j = i + (prng.next_u64() % (sample.len() - i) as u64) as usize;
+            order.swap(i, j);
+        }
+        let initial = &order[..centroids];
+
+        let mut codebooks = Vec::with_capacity(subspaces * centroids * sub_dim);
+        let mut assignments = vec![!0; sample.len()];
+        let mut sums = vec![0.0f64; centroids * sub_dim];
+        let mut counts = vec![0usize; centroids];
+        for subspace in 0..subspaces {
+            let part = subspace * sub_dim..(subspace + 1) * sub_dim;
+            let mut codebook: Vec<f32> = initial
+                .iter()
+                .flat_map(|&ix| sample[ix].as_ref()[part.clone()].iter().copied())
+                .collect();
+            assignments.iter_mut().for_each(|a| *a = !0);
+            for _ in 0..iterations {
+                let mut changed = false;
+                sums.iter_mut().for_each(|s| *s = 0.0);
+                counts.iter_mut().for_each(|c| *c = 0);
+                for (v, assignment) in sample.iter().zip(&mut assignments) {
+                    let v = &v.as_ref()[part.clone()];
+                    let nearest = nearest_centroid(&codebook, sub_dim, v);
+                    changed |= *assignment != nearest;
+                    *assignment = nearest;
+                    counts[nearest] += 1;
+                    for (sum, &x) in sums[nearest * sub_dim..].iter_mut().zip(v) {
+                        *sum += f64::from(x);
+                    }
+                }
+                if !changed {
+                    break;
+                }
+                // A centroid which lost all of its vectors stays where it was.
+                for (centroid, &count) in counts.iter().enumerate().filter(|&(_, &c)| c != 0) {
+                    let range = centroid * sub_dim..(centroid + 1) * sub_dim;
+                    for (c, &sum) in codebook[range.clone()].iter_mut().zip(&sums[range]) {
+                        *c = (sum / count as f64) as f32;
+                    }
+                }
+            }
+            codebooks.extend_from_slice(&codebook);
+        }
+
+        let mut centroid_distances = Vec::with_capacity(subspaces * centroids * centroids);
+        for codebook in codebooks.chunks(centroids * sub_dim) {
+            for a in codebook.chunks(sub_dim) {
+                for b in codebook.chunks(sub_dim) {
+                    centroid_distances.push(squared_distance(a, b));
+                }
+            }
+        }
+
+        Self {
+            dim,
+            subspaces,
+            centroids,
+            codebooks,
+            centroid_distances,
+        }
+    }
+
+    /// The dimension of the vectors.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The number of subspaces, which is the number of bytes in every encoded vector.
+    pub fn subspaces(&self) -> usize {
+        self.subspaces
+    }
+
+    /// The number of centroids in every subspace.
+    pub fn centroids(&self) -> usize {
+        self.centroids
+    }
+
+    fn sub_dim(&self) -> usize {
+        self.dim / self.subspaces
+    }
+
+    fn codebook(&self, subspace: usize) -> &[f32] {
+        let len = self.centroids * self.sub_dim();
+        &self.codebooks[subspace * len..(subspace + 1) * len]
+    }
+
+    /// Compresses a vector into the index of the closest centroid in every subspace, to be inserted.
+    ///
+    /// Panics if the vector has the wrong dimension.
+    pub fn encode(&self, v: &[f32]) -> PqFeature {
+        assert_eq!(v.len(), self.dim, "the vector has the wrong dimension");
+        let sub_dim = self.sub_dim();
+        PqFeature::Codes(
+            v.chunks(sub_dim)
+                .enumerate()
+                .map(|(subspace, part)| {
+                    nearest_centroid(self.codebook(subspace), sub_dim, part) as u8
+                })
+                .collect(),
+        )
+    }
+
+    /// Finds the squared distance from a vector to every centroid, to search for its nearest encoded vectors.
+    ///
+    /// Panics if the vector has the wrong dimension.
+    pub fn query(&self, v: &[f32]) -> PqFeature {
+        assert_eq!(v.len(), self.dim, "the vector has the wrong dimension");
+        let sub_dim = self.sub_dim();
+        PqFeature::Query {
+            vector: v.into(),
+            table: v
+                .chunks(sub_dim)
+                .enumerate()
+                .flat_map(|(subspace, part)| {
+                    self.codebook(subspace)
+                        .chunks(sub_dim)
+                        .map(move |centroid| squared_distance(part, centroid))
+                })
+                .collect(),
+        }
+    }
+
+    /// Approximates an encoded vector with the centroids it was encoded to.
+    pub fn decode(&self, codes: &[u8]) -> Vec<f32> {
+        let sub_dim = self.sub_dim();
+        codes
+            .iter()
+            .enumerate()
+            .flat_map(|(subspace, &code)| {
+                let start = code as usize * sub_dim;
+                self.codebook(subspace)[start..start + sub_dim]
+                    .iter()
+                    .copied()
+            })
+            .collect()
+    }
+}
+
+impl Metric<PqFeature> for PqCodec {
+    type Unit = OrderedF32;
+
+    fn distance(&self, a: &PqFeature, b: &PqFeature) -> OrderedF32 {
+        let k = self.centroids;
+        let distance: f32 = match (a, b) {
+            (PqFeature::Codes(a), PqFeature::Codes(b)) => a
+                .iter()
+                .zip(b.iter())
+                .enumerate()
+                .map(|(subspace, (&a, &b))| {
+                    self.centroid_distances[(subspace * k + a as usize) * k + b as usize]
+                })
+                .sum(),
+            (PqFeature::Query { table, .. }, PqFeature::Codes(codes))
+            | (PqFeature::Codes(codes), PqFeature::Query { table, .. }) => codes
+                .iter()
+                .enumerate()
+                .map(|(subspace, &code)| table[subspace * k + code as usize])
+                .sum(),
+            (PqFeature::Query { vector: a, .. }, PqFeature::Query { vector: b, .. }) => {
+                squared_distance(a, b)
+            }
+        };
+        OrderedF32::new(distance)
+    }
+}
+
+/// Replaces the distances of neighbors with their exact squared euclidean distance to `query`, and sorts them.
+///
+/// The approximate distances from a [`PqCodec`] or [`ScalarCodec`](crate::ScalarCodec) can put the neighbors
+/// in the wrong order, so search for more neighbors than needed and rerank them with the full-precision vector
+/// of every item, which can be kept anywhere, such as on disk. The distances have the same units as the codecs.
+///
+/// Panics if a distance is NaN.
+pub fn rerank<'a, F>(query: &[f32], neighbors: &mut [Neighbor<OrderedF32>], mut vector: F)
+where
+    F: FnMut(usize) -> &'a [f32],
+{
+    for neighbor in neighbors.iter_mut() {
+        neighbor.distance = OrderedF32::new(squared_distance(query, vector(neighbor.index)));
+    }
+    neighbors.sort_by_key(|neighbor| neighbor.distance);
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(&a, &b)| (a - b) * (a - b)).sum()
+}
+
+fn nearest_centroid(codebook: &[f32], sub_dim: usize, v: &[f32]) -> usize {
+    codebook
+        .chunks(sub_dim)
+        .map(|centroid| squared_distance(v, centroid).to_bits())
+        .enumerate()
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(centroid, _)| centroid)
+        .unwrap()
+}
@@ -0,0 +1,155 @@
+use crate::OrderedF32;
+use alloc::{boxed::Box, vec::Vec};
+use space::Metric;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A scalar quantization codec, which compresses every dimension of `f32` vectors to one byte.
+///
+/// Every dimension is scaled from the range it has in the sample given to [`ScalarCodec::train`] to the range
+/// of a `u8`, so a vector takes a quarter of the memory. Values outside of the range are clamped to it.
+///
+/// The codec is the [`Metric`] of a [`Hnsw`](crate::Hnsw) over [`ScalarFeature`]. Insert the features made by
+/// [`ScalarCodec::encode`], and search with the features made by [`ScalarCodec::query`], which keep the query
+/// in full precision. The distance is the squared euclidean distance as an [`OrderedF32`], like
+/// [`PqCodec`](crate::PqCodec), so [`rerank`](crate::rerank) can rescore the nearest neighbors with their
+/// full-precision vectors.
+///
+/// The distance panics if it is NaN, which happens when the vectors or the sample contain NaN.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScalarCodec {
+    /// The smallest value of every dimension.
+    min: Vec<f32>,
+    /// The difference between consecutive codes of every dimension.
+    scale: Vec<f32>,
+}
+
+/// A feature compressed by a [`ScalarCodec`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScalarFeature {
+    /// The code of every dimension, made by [`ScalarCodec::encode`], which is what a HNSW stores.
+    Codes(Box<[u8]>),
+    /// The full-precision vector made by [`ScalarCodec::query`] to search with.
+    Query(Box<[f32]>),
+}
+
+impl ScalarFeature {
+    /// Gets the codes if this was made by [`ScalarCodec::encode`].
+    pub fn codes(&self) -> Option<&[u8]> {
+        match self {
+            ScalarFeature::Codes(codes) => Some(codes),
+            ScalarFeature::Query(_) => None,
+        }
+    }
+}
+
+impl ScalarCodec {
+    /// Finds the range of every dimension in a sample of vectors.
+    ///
+    /// The sample should be representative of the vectors which will be encoded.
+    ///
+    /// Panics if the sample is empty or the vectors don't all have the same dimension.
+    pub fn train<P: AsRef<[f32]>>(sample: &[P]) -> Self {
+        assert!(!sample.is_empty(), "the sample must not be empty");
+        let mut min = sample[0].as_ref().to_vec();
+        let mut max = min.clone();
+        for v in sample {
+            let v = v.as_ref();
+            assert_eq!(
+                v.len(),
+                min.len(),
+                "the sample vectors must have the same dimension"
+            );
+            for ((min, max), &x) in min.iter_mut().zip(&mut max).zip(v) {
+                *min = min.min(x);
+                *max = max.max(x);
+            }
+        }
+        let scale = min
+            .iter()
+            .zip(&max)
+            .map(|(&min, &max)| (max - min) / f32::from(u8::MAX))
+            .collect();
+        Self { min, scale }
+    }
+
+    /// The dimension of the vectors.
+    pub fn dim(&self) -> usize {
+        self.min.len()
+    }
+
+    /// Compresses a vector into one code for every dimension, to be inserted.
+    ///
+    /// Panics if the vector has the wrong dimension.
+    pub fn encode(&self, v: &[f32]) -> ScalarFeature {
+        assert_eq!(v.len(), self.dim(), "the vector has the wrong dimension");
+        ScalarFeature::Codes(
+            v.iter()
+                .zip(&self.min)
+                .zip(&self.scale)
+                .map(|((&x, &min), &scale)| {
+                    if scale > 0.0 {
+                        libm::roundf((x - min) / scale).clamp(0.0, f32::from(u8::MAX)) as u8
+                    } else {
+                        0
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Keeps a vector in full precision, to search for its nearest encoded vectors.
+    ///
+    /// Panics if the vector has the wrong dimension.
+    pub fn query(&self, v: &[f32]) -> ScalarFeature {
+        assert_eq!(v.len(), self.dim(), "the vector has the wrong dimension");
+        ScalarFeature::Query(v.into())
+    }
+
+    /// Approximates an encoded vector from its codes.
+    pub fn decode(&self, codes: &[u8]) -> Vec<f32> {
+        codes
+            .iter()
+            .zip(&self.min)
+            .zip(&self.scale)
+            .map(|((&code, &min), &scale)| min + f32::from(code) * scale)
+            .collect()
+    }
+}
+
+impl Metric<ScalarFeature> for ScalarCodec {
+    type Unit = OrderedF32;
+
+    fn distance(&self, a: &ScalarFeature, b: &ScalarFeature) -> OrderedF32 {
+        let distance: f32 = match (a, b) {
+            (ScalarFeature::Codes(a), ScalarFeature::Codes(b)) => a
+                .iter()
+                .zip(b.iter())
+                .zip(&self.scale)
+                .map(|((&a, &b), &scale)| {
+                    let difference = (f32::from(a) - f32::from(b)) * scale;
+                    difference * difference
+                })
+                .sum(),
+            (ScalarFeature::Query(query), ScalarFeature::Codes(codes))
+            | (ScalarFeature::Codes(codes), ScalarFeature::Query(query)) => query
+                .iter()
+                .zip(codes.iter())
+                .zip(self.min.iter().zip(&self.scale))
+                .map(|((&x, &code), (&min, &scale))| {
+                    let difference = x - (min + f32::from(code) * scale);
+                    difference * difference
+                })
+                .sum(),
+            (ScalarFeature::Query(a), ScalarFeature::Query(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(&a, &b)| (a - b) * (a - b))
+                .sum(),
+        };
+        OrderedF32::new(distance)
+    }
+}
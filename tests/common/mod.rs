@@ -0,0 +1,73 @@
+//! Helpers shared by the tests.
+#![allow(dead_code)]
+
+use hnsw::{Hnsw, Params, Searcher};
+use rand::distributions::Standard;
+use rand::Rng;
+use rand_core::{RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use space::Metric;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Hamming;
+
+impl Metric<u64> for Hamming {
+    type Unit = u32;
+
+    fn distance(&self, &a: &u64, &b: &u64) -> u32 {
+        (a ^ b).count_ones()
+    }
+}
+
+impl Metric<u32> for Hamming {
+    type Unit = u32;
+
+    fn distance(&self, &a: &u32, &b: &u32) -> u32 {
+        (a ^ b).count_ones()
+    }
+}
+
+/// Generates the same random features every time.
+pub fn random_features(count: usize) -> Vec<u64> {
+    Pcg64::from_seed([5; 32])
+        .sample_iter(Standard)
+        .take(count)
+        .collect()
+}
+
+/// Inserts `len` random features and then removes items `7` and `100`.
+pub fn test_hnsw(params: Params, len: usize) -> Hnsw<Hamming, u64, Pcg64, 12, 24> {
+    let mut hnsw = Hnsw::new_params(Hamming, params);
+    let mut searcher = Searcher::default();
+    for feature in random_features(len) {
+        hnsw.insert(feature, &mut searcher);
+    }
+    hnsw.remove(7);
+    hnsw.remove(100);
+    hnsw
+}
+
+/// Gets the item and neighbors of every node on every layer, after checking the graph with `validate`.
+pub fn graph<Met, T, R, const M: usize, const M0: usize>(
+    hnsw: &Hnsw<Met, T, R, M, M0>,
+) -> Vec<Vec<(usize, Vec<usize>)>>
+where
+    Met: Metric<T>,
+    R: RngCore,
+{
+    hnsw.validate().unwrap();
+    (0..hnsw.layers())
+        .map(|level| {
+            (0..hnsw.layer_len(level))
+                .map(|node| {
+                    (
+                        hnsw.layer_item_id(level, node),
+                        hnsw.neighbors(level, node).collect(),
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use bitarray::{BitArray, Hamming};
+use common::graph;
+use hnsw::*;
+use rand::distributions::Standard;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::Neighbor;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// This can be adjusted lower if it is too slow.
+const SEARCH_SPACE_SIZE: usize = 1 << 12;
+
+#[test]
+fn search_while_inserting() {
+    let space = Pcg64::from_seed([5; 32])
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(SEARCH_SPACE_SIZE)
+        .collect::<Vec<BitArray<16>>>();
+
+    let mut sequential: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::new(Hamming);
+    let mut searcher = Searcher::default();
+    for &feature in &space {
+        sequential.insert(feature, &mut searcher);
+    }
+
+    let hnsw: ConcurrentHnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::new(Hamming).into();
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        for seed in 0..3 {
+            let hnsw = &hnsw;
+            let done = &done;
+            scope.spawn(move || {
+                let mut prng = Pcg64::from_seed([seed; 32]);
+                let mut searcher = Searcher::default();
+                let mut output = [Neighbor {
+                    index: !0,
+                    distance: !0,
+                }; 8];
+                let mut searches = 0;
+                while !done.load(Ordering::Acquire) {
+                    // The search results must always be real items with the right distances in order.
+                    let query = BitArray::new(prng.gen());
+                    let found = hnsw.nearest(&query, 24, &mut searcher, &mut output);
+                    for pair in found.windows(2) {
+                        assert!(pair[0].distance <= pair[1].distance);
+                    }
+                    for neighbor in found.iter() {
+                        assert_eq!(
+                            neighbor.distance,
+                            query.distance(&hnsw.feature(neighbor.index))
+                        );
+                    }
+
+                    // The neighbors of a node must never be seen partially changed, which would show up as
+                    // duplicate neighbors, the node being its own neighbor, or neighbors that don't exist.
+                    let level = prng.gen_range(0..hnsw.layers());
+                    let len = hnsw.layer_len(level);
+                    if len != 0 {
+                        let node = prng.gen_range(0..len);
+                        let neighbors = hnsw.neighbors(level, node);
+                        let unique = neighbors.iter().copied().collect::<HashSet<usize>>();
+                        assert_eq!(unique.len(), neighbors.len());
+                        assert!(!unique.contains(&node));
+                        let len = hnsw.layer_len(level);
+                        assert!(neighbors.iter().all(|&neighbor| neighbor < len));
+                    }
+                    searches += 1;
+                }
+                assert!(searches > 0);
+            });
+        }
+
+        let mut searcher = Searcher::default();
+        for (item, &feature) in space.iter().enumerate() {
+            assert_eq!(hnsw.insert(feature, &mut searcher), item);
+        }
+        done.store(true, Ordering::Release);
+    });
+
+    // Inserts happen one at a time, so the searches shouldn't have changed anything.
+    let hnsw = hnsw.into_inner();
+    assert_eq!(hnsw.len(), SEARCH_SPACE_SIZE);
+    assert_eq!(graph(&hnsw), graph(&sequential));
+}
+
+#[test]
+fn remove_concurrent() {
+    let mut searcher = Searcher::default();
+    let hnsw: ConcurrentHnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::new(Hamming).into();
+    let space = Pcg64::from_seed([5; 32])
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(64)
+        .collect::<Vec<BitArray<16>>>();
+    for &feature in &space {
+        hnsw.insert(feature, &mut searcher);
+    }
+    assert!(hnsw.remove(3));
+    assert!(!hnsw.remove(3));
+
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+    let found = hnsw.nearest(&space[3], 64, &mut searcher, &mut output);
+    assert_ne!(found[0].index, 3);
+
+    let hnsw = hnsw.into_inner();
+    assert!(hnsw.is_removed(3));
+    assert_eq!(hnsw.tombstones(), 1);
+}
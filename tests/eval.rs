@@ -0,0 +1,70 @@
+#![cfg(feature = "std")]
+
+use hnsw::eval::*;
+use hnsw::metrics::Hamming;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+
+type Flat = FlatIndex<Hamming, [u8; 8]>;
+type TestHnsw = Hnsw<Hamming, [u8; 8], Pcg64, 12, 24>;
+
+/// Gives the same items in a flat index and a HNSW, and the queries.
+fn setup() -> (Flat, TestHnsw, Vec<[u8; 8]>) {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let mut searcher = Searcher::default();
+    let mut flat = FlatIndex::new(Hamming);
+    let mut hnsw = Hnsw::new(Hamming);
+    for _ in 0..2048 {
+        let feature: [u8; 8] = prng.gen();
+        flat.insert(feature, &mut searcher);
+        hnsw.insert(feature, &mut searcher);
+    }
+    let queries = (0..100).map(|_| prng.gen()).collect();
+    (flat, hnsw, queries)
+}
+
+#[test]
+fn exact_recall() {
+    let (flat, _, queries) = setup();
+    let truth = ground_truth(&flat, &queries, 10);
+    assert!(truth.iter().all(|neighbors| neighbors.len() == 10));
+    assert_eq!(recall_at_k(&flat, &queries, &truth, 10, 0), 1.0);
+    // Asking for fewer neighbors only uses the nearest of the ground truth.
+    assert_eq!(recall_at_k(&flat, &queries, &truth, 3, 0), 1.0);
+
+    // Recall is measured by distance, so the ground truth can name other items tied with the ones found.
+    let mut tied = truth.clone();
+    for neighbors in &mut tied {
+        neighbors[9].index = !0;
+    }
+    assert_eq!(recall_at_k(&flat, &queries, &tied, 10, 0), 1.0);
+}
+
+#[test]
+fn hnsw_sweep() {
+    let (flat, hnsw, queries) = setup();
+    let truth = ground_truth(&flat, &queries, 10);
+    let sweep = ef_sweep(&hnsw, &queries, &truth, 10, [10, 40, 160]);
+    assert_eq!(
+        sweep.iter().map(|m| m.ef).collect::<Vec<_>>(),
+        [10, 40, 160]
+    );
+    for pair in sweep.windows(2) {
+        assert!(pair[0].recall <= pair[1].recall);
+        assert!(pair[0].distances < pair[1].distances);
+    }
+    for measurement in &sweep {
+        assert!(measurement.distances <= 2048.0);
+    }
+    assert!(sweep[2].recall >= 0.9, "{:?}", sweep);
+    assert_eq!(
+        recall_at_k(&hnsw, &queries, &truth, 10, 160),
+        sweep[2].recall
+    );
+
+    let exact = ef_sweep(&flat, &queries, &truth, 10, [1]);
+    assert_eq!(exact[0].recall, 1.0);
+    assert_eq!(exact[0].distances, 2048.0);
+}
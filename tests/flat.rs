@@ -0,0 +1,69 @@
+use hnsw::metrics::Hamming;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Knn, KnnPoints, Metric, Neighbor};
+
+fn test_features() -> Vec<[u8; 8]> {
+    Pcg64::from_seed([5; 32])
+        .sample_iter(rand::distributions::Standard)
+        .take(512)
+        .collect()
+}
+
+#[test]
+fn exact_nearest() {
+    let features = test_features();
+    let mut searcher = Searcher::default();
+    let mut flat = FlatIndex::new(Hamming);
+    assert!(flat.is_empty());
+    for (ix, &feature) in features.iter().enumerate() {
+        assert_eq!(flat.insert(feature, &mut searcher), ix);
+    }
+    assert_eq!(flat.len(), 512);
+
+    let query = [0x55; 8];
+    let mut expected: Vec<Neighbor<u32>> = features
+        .iter()
+        .enumerate()
+        .map(|(index, feature)| Neighbor {
+            index,
+            distance: Hamming.distance(&query, feature),
+        })
+        .collect();
+    expected.sort_by_key(|n| (n.distance, n.index));
+
+    let mut dest = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 16];
+    let found = flat.nearest(&query, 0, &mut searcher, &mut dest);
+    assert_eq!(found, &expected[..16]);
+
+    // There are fewer items than neighbors asked for.
+    let mut small = FlatIndex::new(Hamming);
+    small.insert(features[0], &mut searcher);
+    small.insert(features[1], &mut searcher);
+    assert_eq!(small.nearest(&query, 0, &mut searcher, &mut dest).len(), 2);
+}
+
+#[test]
+fn same_traits_as_hnsw() {
+    let features = test_features();
+    let mut searcher = Searcher::default();
+    let mut flat = FlatIndex::new(Hamming);
+    let mut hnsw: Hnsw<Hamming, [u8; 8], Pcg64, 12, 24> = Hnsw::new(Hamming);
+    for &feature in &features {
+        flat.insert(feature, &mut searcher);
+        hnsw.insert(feature, &mut searcher);
+    }
+
+    let query = features[7];
+    let exact = flat.knn(&query, 4);
+    assert_eq!(exact.len(), 4);
+    assert_eq!(exact[0].index, 7);
+    assert_eq!(flat.get_point(exact[0].index), &query);
+    assert_eq!(flat.nn(&query), hnsw.nn(&query));
+    assert_eq!(flat.knn(&query, 1000).len(), 512);
+}
@@ -0,0 +1,193 @@
+#![cfg(feature = "frozen")]
+
+mod common;
+
+use common::Hamming;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::Neighbor;
+use std::fs::File;
+use std::io::BufWriter;
+
+fn test_hnsw() -> Hnsw<Hamming, u64, Pcg64, 12, 24> {
+    common::test_hnsw(Params::new().ef_construction(100), 2000)
+}
+
+/// Saves the HNSW to a temporary file and memory-maps it.
+fn map(hnsw: &Hnsw<Hamming, u64, Pcg64, 12, 24>, name: &str) -> memmap2::Mmap {
+    let path = std::env::temp_dir().join(format!("hnsw-{}-{}", std::process::id(), name));
+    hnsw.save_frozen(BufWriter::new(File::create(&path).unwrap()))
+        .unwrap();
+    let map = unsafe { memmap2::Mmap::map(&File::open(&path).unwrap()).unwrap() };
+    std::fs::remove_file(&path).unwrap();
+    map
+}
+
+#[test]
+fn same_as_hnsw() {
+    let hnsw = test_hnsw();
+    let map = map(&hnsw, "same");
+    let frozen: FrozenHnsw<Hamming, u64, 12, 24> = FrozenHnsw::from_bytes(Hamming, &map).unwrap();
+
+    assert_eq!(frozen.len(), hnsw.len());
+    assert_eq!(frozen.layers(), hnsw.layers());
+    assert_eq!(frozen.tombstones(), 2);
+    assert!(frozen.is_removed(7) && frozen.is_removed(100) && !frozen.is_removed(8));
+    for level in 0..hnsw.layers() {
+        assert_eq!(frozen.layer_len(level), hnsw.layer_len(level));
+        for node in 0..hnsw.layer_len(level) {
+            assert_eq!(
+                frozen.layer_item_id(level, node),
+                hnsw.layer_item_id(level, node)
+            );
+            assert!(frozen
+                .neighbors(level, node)
+                .eq(hnsw.neighbors(level, node)));
+        }
+    }
+
+    let mut searcher = Searcher::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+    let mut frozen_output = output;
+    let mut prng = Pcg64::from_seed([6; 32]);
+    for _ in 0..100 {
+        let query = prng.gen();
+        for level in 0..hnsw.layers() {
+            let found = hnsw.search_layer(&query, 24, level, &mut searcher, &mut output);
+            let frozen_found =
+                frozen.search_layer(&query, 24, level, &mut searcher, &mut frozen_output);
+            assert_eq!(found, frozen_found);
+        }
+        let found =
+            hnsw.nearest_filtered(&query, 24, |item| item % 3 == 0, &mut searcher, &mut output);
+        let frozen_found = frozen.nearest_filtered(
+            &query,
+            24,
+            |item| item % 3 == 0,
+            &mut searcher,
+            &mut frozen_output,
+        );
+        assert_eq!(found, frozen_found);
+    }
+}
+
+#[test]
+fn empty() {
+    let hnsw: Hnsw<Hamming, u64, Pcg64, 12, 24> = Hnsw::new(Hamming);
+    let map = map(&hnsw, "empty");
+    let frozen: FrozenHnsw<Hamming, u64, 12, 24> = FrozenHnsw::from_bytes(Hamming, &map).unwrap();
+    assert!(frozen.is_empty());
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+    assert!(frozen
+        .nearest(&0, 24, &mut Searcher::default(), &mut output)
+        .is_empty());
+}
+
+#[test]
+fn errors() {
+    let hnsw = test_hnsw();
+    let map = map(&hnsw, "errors");
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u64, 16, 24>::from_bytes(Hamming, &map),
+        Err(PersistError::MismatchedM {
+            expected: 16,
+            found: 12
+        })
+    ));
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u64, 12, 32>::from_bytes(Hamming, &map),
+        Err(PersistError::MismatchedM0 {
+            expected: 32,
+            found: 24
+        })
+    ));
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u32, 12, 24>::from_bytes(Hamming, &map),
+        Err(PersistError::MismatchedFeatureSize {
+            expected: 4,
+            found: 8
+        })
+    ));
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u64, 12, 24>::from_bytes(Hamming, &map[1..]),
+        Err(PersistError::Misaligned)
+    ));
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u64, 12, 24>::from_bytes(Hamming, &map[..map.len() - 8]),
+        Err(PersistError::Corrupt(_))
+    ));
+
+    let mut words = vec![0u64; map.len() / 8];
+    bytemuck::cast_slice_mut(&mut words).copy_from_slice(&map[..]);
+    bytemuck::cast_slice_mut::<u64, u8>(&mut words)[0] = b'X';
+    assert!(matches!(
+        FrozenHnsw::<Hamming, u64, 12, 24>::from_bytes(Hamming, bytemuck::cast_slice(&words)),
+        Err(PersistError::BadMagic)
+    ));
+}
+
+/// Opens the saved HNSW after changing its words.
+fn open_changed(map: &[u8], change: impl FnOnce(&mut Vec<u64>)) -> Result<(), PersistError> {
+    let mut words = vec![0u64; map.len() / 8];
+    bytemuck::cast_slice_mut(&mut words).copy_from_slice(map);
+    change(&mut words);
+    FrozenHnsw::<Hamming, u64, 12, 24>::from_bytes(Hamming, bytemuck::cast_slice(&words))
+        .map(|_| ())
+}
+
+#[test]
+fn corrupt_graph() {
+    let hnsw = test_hnsw();
+    let map = map(&hnsw, "corrupt");
+    let len = hnsw.len();
+    // The header is the magic, version and feature size, then `M`, `M0`, the length, the number of layers,
+    // the number of tombstones and the length of each layer.
+    let layers = hnsw.layers() - 1;
+    let zero = 7 + layers;
+    let first_layer = zero + len * 24;
+
+    assert!(open_changed(&map, |_| {}).is_ok());
+    assert!(matches!(
+        open_changed(&map, |words| words.push(0)),
+        Err(PersistError::Corrupt(_))
+    ));
+    assert!(matches!(
+        open_changed(&map, |words| words[6] = 3u64.to_le()),
+        Err(PersistError::Invalid(ValidationError::TombstoneCount {
+            tombstones: 3,
+            removed: 2
+        }))
+    ));
+    assert!(matches!(
+        open_changed(&map, |words| words[zero] = (len as u64).to_le()),
+        Err(PersistError::Invalid(ValidationError::NeighborOutOfRange {
+            level: 0,
+            node: 0,
+            neighbor
+        })) if neighbor == len
+    ));
+    assert!(matches!(
+        open_changed(&map, |words| words[first_layer] = 5000u64.to_le()),
+        Err(PersistError::Invalid(ValidationError::ItemOutOfRange {
+            level: 1,
+            node: 0,
+            item: 5000
+        }))
+    ));
+    assert!(matches!(
+        open_changed(&map, |words| words[first_layer + 1] = 5000u64.to_le()),
+        Err(PersistError::Invalid(ValidationError::NextNodeOutOfRange {
+            level: 1,
+            node: 0,
+            next_node: 5000
+        }))
+    ));
+}
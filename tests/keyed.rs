@@ -0,0 +1,105 @@
+mod common;
+
+use common::{random_features, Hamming};
+use hnsw::*;
+use rand_pcg::Pcg64;
+use space::Neighbor;
+
+type Keyed = KeyedHnsw<String, Hamming, u64, Pcg64, 12, 24>;
+
+fn test_keyed() -> (Keyed, Vec<u64>) {
+    let mut searcher = Searcher::default();
+    let mut keyed = Keyed::new(Hnsw::new(Hamming));
+    let features = random_features(256);
+    for (ix, &feature) in features.iter().enumerate() {
+        keyed
+            .insert(format!("item-{}", ix), feature, &mut searcher)
+            .unwrap();
+    }
+    (keyed, features)
+}
+
+/// Finds the key of the nearest item.
+fn nearest_key(keyed: &Keyed, feature: u64) -> Option<String> {
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+    let key = keyed
+        .nearest(&feature, 64, &mut Searcher::default(), &mut output)
+        .next()
+        .map(|n| n.index.clone());
+    key
+}
+
+#[test]
+fn keys() {
+    let (mut keyed, features) = test_keyed();
+    let mut searcher = Searcher::default();
+    assert_eq!(keyed.len(), 256);
+    assert_eq!(keyed.get("item-3"), Some(&features[3]));
+    assert_eq!(keyed.get("missing"), None);
+    assert_eq!(nearest_key(&keyed, features[3]).unwrap(), "item-3");
+
+    // Duplicate keys are rejected and given back.
+    let duplicate = keyed
+        .insert("item-3".to_string(), 7, &mut searcher)
+        .unwrap_err();
+    assert_eq!(duplicate.key, "item-3");
+    assert_eq!(duplicate.feature, 7);
+    assert_eq!(keyed.len(), 256);
+
+    // A removed key can be used again.
+    assert!(keyed.remove("item-3"));
+    assert!(!keyed.remove("item-3"));
+    assert!(!keyed.contains_key("item-3"));
+    assert_ne!(nearest_key(&keyed, features[3]).unwrap(), "item-3");
+    keyed
+        .insert("item-3".to_string(), !features[3], &mut searcher)
+        .unwrap();
+    assert_eq!(nearest_key(&keyed, !features[3]).unwrap(), "item-3");
+
+    assert!(keyed.update("item-4", !features[4], &mut searcher));
+    assert!(!keyed.update("missing", 0, &mut searcher));
+    assert_eq!(nearest_key(&keyed, !features[4]).unwrap(), "item-4");
+}
+
+#[test]
+fn compact_keeps_keys() {
+    let (mut keyed, features) = test_keyed();
+    for ix in (0..256).step_by(3) {
+        assert!(keyed.remove(&format!("item-{}", ix)));
+    }
+    keyed.compact();
+    assert_eq!(keyed.len(), keyed.hnsw().len());
+    for (ix, feature) in features.iter().enumerate() {
+        let key = format!("item-{}", ix);
+        if ix % 3 == 0 {
+            assert_eq!(keyed.get(&key), None);
+        } else {
+            let item = keyed.item(&key).unwrap();
+            assert_eq!(keyed.key(item), &key);
+            assert_eq!(keyed.hnsw().feature(item), feature);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_keys() {
+    let (mut keyed, features) = test_keyed();
+    keyed.remove("item-5");
+    let json = serde_json::to_string(&keyed).unwrap();
+    let deserialized: Keyed = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.len(), 255);
+    assert!(!deserialized.contains_key("item-5"));
+    assert_eq!(deserialized.get("item-6"), Some(&features[6]));
+    assert_eq!(nearest_key(&deserialized, features[6]).unwrap(), "item-6");
+
+    let duplicated = json.replace("\"item-6\"", "\"item-7\"");
+    assert!(serde_json::from_str::<Keyed>(&duplicated)
+        .map(|_| ())
+        .unwrap_err()
+        .to_string()
+        .contains("two items have the same key"));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{random_features, Hamming};
+use hnsw::*;
+use rand_pcg::Pcg64;
+use space::{KnnInsert, KnnMap, Neighbor};
+
+type Map = HnswMap<Hamming, u64, String, Pcg64, 12, 24>;
+
+fn test_map() -> (Map, Vec<u64>) {
+    let mut searcher = Searcher::default();
+    let mut map = Map::new(Hnsw::new(Hamming));
+    let features = random_features(256);
+    for (ix, &feature) in features.iter().enumerate() {
+        assert_eq!(
+            map.insert(feature, format!("value-{}", ix), &mut searcher),
+            ix
+        );
+    }
+    (map, features)
+}
+
+/// Finds the value of the nearest item.
+fn nearest_value(map: &Map, feature: u64) -> Option<String> {
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+    let value = map
+        .nearest(&feature, 64, &mut Searcher::default(), &mut output)
+        .next()
+        .map(|(_, value)| value.clone());
+    value
+}
+
+#[test]
+fn values() {
+    let (mut map, features) = test_map();
+    assert_eq!(map.value(3), "value-3");
+    assert_eq!(nearest_value(&map, features[3]).unwrap(), "value-3");
+
+    *map.value_mut(3) = "changed".to_string();
+    assert_eq!(nearest_value(&map, features[3]).unwrap(), "changed");
+
+    map.update(4, !features[4], &mut Searcher::default());
+    assert_eq!(nearest_value(&map, !features[4]).unwrap(), "value-4");
+
+    let item = KnnInsert::insert(&mut map, 42, "inserted".to_string());
+    assert_eq!(item, 256);
+    let (neighbor, value) = map.knn_values(&42, 1).into_iter().next().unwrap();
+    assert_eq!(neighbor.index, item);
+    assert_eq!(value, "inserted");
+    assert_eq!(map.get_value(item), "inserted");
+}
+
+#[test]
+fn compact_keeps_values() {
+    let (mut map, features) = test_map();
+    for item in (0..256).step_by(3) {
+        assert!(map.remove(item));
+    }
+    let mapping = map.compact();
+    assert_eq!(map.len(), map.hnsw().len());
+    for (ix, feature) in features.iter().enumerate() {
+        if ix % 3 == 0 {
+            assert_eq!(mapping[ix], !0);
+        } else {
+            assert_eq!(map.value(mapping[ix]), &format!("value-{}", ix));
+            assert_eq!(map.feature(mapping[ix]), feature);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_values() {
+    let (map, features) = test_map();
+    let json = serde_json::to_string(&map).unwrap();
+    let deserialized: Map = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.len(), 256);
+    assert_eq!(
+        nearest_value(&deserialized, features[6]).unwrap(),
+        "value-6"
+    );
+
+    let missing = json.replace(",\"value-255\"", "");
+    assert!(serde_json::from_str::<Map>(&missing)
+        .map(|_| ())
+        .unwrap_err()
+        .to_string()
+        .contains("the number of values"));
+}
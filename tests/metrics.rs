@@ -0,0 +1,155 @@
+use hnsw::metrics::*;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Metric, Neighbor};
+
+fn random_vector(prng: &mut Pcg64, len: usize) -> Vec<f32> {
+    (0..len).map(|_| prng.gen_range(-1.0..1.0)).collect()
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= 1e-4 * (1.0 + a.abs().max(b.abs()))
+}
+
+#[test]
+fn float_distances() {
+    let a = [1.0f32, 2.0, 3.0];
+    let b = [4.0f32, 6.0, 3.0];
+    assert_eq!(Euclidean.distance(&a, &b).get(), 5.0);
+    assert_eq!(SquaredEuclidean.distance(&a, &b).get(), 25.0);
+    assert_eq!(InnerProduct.distance(&a, &b).get(), -25.0);
+    assert!(close(
+        Cosine.distance(&a, &b).get(),
+        1.0 - 25.0 / (14.0f32.sqrt() * 61.0f32.sqrt())
+    ));
+    assert_eq!(Cosine.distance(&a, &[0.0; 3]).get(), 1.0);
+    assert_eq!(Cosine.distance(&a, &a).get(), 0.0);
+
+    // The slice and `Vec` impls give the same distances as the array impls.
+    assert_eq!(
+        Euclidean.distance(&&a[..], &&b[..]),
+        Euclidean.distance(&a, &b)
+    );
+    assert_eq!(
+        Cosine.distance(&a.to_vec(), &b.to_vec()),
+        Cosine.distance(&a, &b)
+    );
+}
+
+#[test]
+fn long_vectors() {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    for len in 0..70 {
+        let a = random_vector(&mut prng, len);
+        let b = random_vector(&mut prng, len);
+        let squared: f32 = a.iter().zip(&b).map(|(a, b)| (a - b) * (a - b)).sum();
+        let dot: f32 = a.iter().zip(&b).map(|(a, b)| a * b).sum();
+        assert!(close(SquaredEuclidean.distance(&a, &b).get(), squared));
+        assert!(close(Euclidean.distance(&a, &b).get(), squared.sqrt()));
+        assert!(close(-InnerProduct.distance(&a, &b).get(), dot));
+    }
+}
+
+#[test]
+fn inner_product_order() {
+    let query = [1.0f32, 0.0];
+    let mut products = [-3.0f32, -0.5, 0.0, 0.25, 2.0, 100.0];
+    let mut distances: Vec<OrderedF32> = products
+        .iter()
+        .map(|&x| InnerProduct.distance(&query, &[x, 7.0]))
+        .collect();
+    // A greater inner product is nearer.
+    distances.sort_unstable();
+    products.reverse();
+    let sorted: Vec<f32> = distances
+        .into_iter()
+        .map(InnerProduct::inner_product)
+        .collect();
+    assert_eq!(sorted, products);
+}
+
+#[test]
+fn nan_panics() {
+    let a = [1.0f32, f32::NAN];
+    let b = [1.0f32, 2.0];
+    assert!(std::panic::catch_unwind(|| Euclidean.distance(&a, &b)).is_err());
+    assert!(std::panic::catch_unwind(|| SquaredEuclidean.distance(&a, &b)).is_err());
+    assert!(std::panic::catch_unwind(|| InnerProduct.distance(&a, &b)).is_err());
+    assert!(std::panic::catch_unwind(|| Cosine.distance(&a, &b)).is_err());
+}
+
+#[test]
+fn hamming() {
+    let a = [0b1010_1010u8; 13];
+    let mut b = a;
+    b[0] ^= 0b11;
+    b[12] ^= 0b1000_0000;
+    assert_eq!(Hamming.distance(&a, &b), 3);
+    assert_eq!(Hamming.distance(&&a[..], &&b[..]), 3);
+    assert_eq!(Hamming.distance(&a.to_vec(), &vec![0; 13]), 52);
+}
+
+#[test]
+fn hnsw_with_metric() {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let features: Vec<Vec<f32>> = (0..512).map(|_| random_vector(&mut prng, 24)).collect();
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<Cosine, Vec<f32>, Pcg64, 12, 24> = Hnsw::new(Cosine);
+    for feature in &features {
+        hnsw.insert(feature.clone(), &mut searcher);
+    }
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: OrderedF32::new(f32::INFINITY),
+    }; 1];
+    for (item, feature) in features.iter().enumerate().take(32) {
+        let scaled: Vec<f32> = feature.iter().map(|x| x * 3.0).collect();
+        assert_eq!(
+            hnsw.nearest(&scaled, 64, &mut searcher, &mut neighbors)[0].index,
+            item
+        );
+    }
+}
+
+#[test]
+fn hnsw_with_inner_product() {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let features: Vec<Vec<f32>> = (0..512).map(|_| random_vector(&mut prng, 8)).collect();
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<InnerProduct, Vec<f32>, Pcg64, 12, 24> = Hnsw::new(InnerProduct);
+    for feature in &features {
+        hnsw.insert(feature.clone(), &mut searcher);
+    }
+    let mut found = 0;
+    for _ in 0..32 {
+        let query = random_vector(&mut prng, 8);
+        let mut expected: Vec<OrderedF32> = features
+            .iter()
+            .map(|feature| InnerProduct.distance(&query, feature))
+            .collect();
+        expected.sort_unstable();
+        let mut neighbors = [Neighbor {
+            index: !0,
+            distance: OrderedF32::new(f32::INFINITY),
+        }; 4];
+        let neighbors = hnsw.nearest(&query, 64, &mut searcher, &mut neighbors);
+        // The nearest items have a positive inner product, so their distances are negative.
+        assert!(neighbors[0].distance.get() < 0.0);
+        for neighbor in neighbors.iter() {
+            assert_eq!(
+                neighbor.distance,
+                InnerProduct.distance(&query, &features[neighbor.index])
+            );
+        }
+        if neighbors[0].distance == expected[0] {
+            found += 1;
+        }
+    }
+    assert!(
+        found >= 30,
+        "only {} of 32 nearest neighbors were found",
+        found
+    );
+}
@@ -0,0 +1,91 @@
+use hnsw::metrics::SquaredEuclidean;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Metric, Neighbor};
+
+const DIM: usize = 12;
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Makes vectors with norms which differ a lot, so normalizing them would change the results.
+fn vectors(prng: &mut Pcg64, count: usize) -> Vec<Vec<f32>> {
+    (0..count)
+        .map(|_| {
+            let scale = prng.gen_range(0.1..4.0);
+            (0..DIM)
+                .map(|_| prng.gen_range(-1.0..1.0) * scale)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn transform() {
+    let items = vec![vec![3.0, 4.0], vec![1.0, 0.0], vec![0.0, -2.0]];
+    let transform = MipsTransform::fit(&items);
+    assert_eq!(transform.max_norm(), 5.0);
+    assert_eq!(transform.item(&items[0]), [3.0, 4.0, 0.0]);
+    assert_eq!(transform.item(&items[1]), [1.0, 0.0, 24.0f32.sqrt()]);
+    assert_eq!(transform.query(&[1.0, 2.0]), [1.0, 2.0, 0.0]);
+
+    let query = [-1.0, 0.5];
+    for item in &items {
+        let distance = SquaredEuclidean.distance(&transform.query(&query), &transform.item(item));
+        let inner_product = transform.inner_product(&query, distance);
+        assert!((inner_product - dot(&query, item)).abs() < 1e-5);
+    }
+
+    assert!(std::panic::catch_unwind(|| transform.item(&[6.0, 0.0])).is_err());
+}
+
+#[test]
+fn top_k_matches_brute_force() {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let items = vectors(&mut prng, 1024);
+    let queries = vectors(&mut prng, 50);
+    let transform = MipsTransform::fit(&items);
+
+    let mut searcher = Searcher::default();
+    let params = Params::new().neighbor_selection(NeighborSelection::Heuristic {
+        extend_candidates: false,
+        keep_pruned_connections: true,
+    });
+    let mut hnsw: Hnsw<SquaredEuclidean, Vec<f32>, Pcg64, 12, 24> =
+        Hnsw::new_params(SquaredEuclidean, params);
+    for item in &items {
+        hnsw.insert(transform.item(item), &mut searcher);
+    }
+
+    let mut found = 0;
+    for query in &queries {
+        let mut expected: Vec<usize> = (0..items.len()).collect();
+        expected.sort_by(|&a, &b| {
+            dot(query, &items[b])
+                .partial_cmp(&dot(query, &items[a]))
+                .unwrap()
+        });
+        expected.truncate(10);
+
+        let mut neighbors = [Neighbor {
+            index: !0,
+            distance: OrderedF32::new(f32::INFINITY),
+        }; 10];
+        let neighbors = hnsw.nearest(&transform.query(query), 128, &mut searcher, &mut neighbors);
+        for neighbor in neighbors.iter() {
+            let inner_product = transform.inner_product(query, neighbor.distance);
+            assert!((inner_product - dot(query, &items[neighbor.index])).abs() < 1e-3);
+            if expected.contains(&neighbor.index) {
+                found += 1;
+            }
+        }
+    }
+    assert!(
+        found >= 490,
+        "only {} of 500 neighbors were in the top 10",
+        found
+    );
+}
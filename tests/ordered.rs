@@ -0,0 +1,88 @@
+use core::convert::TryFrom;
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Metric, Neighbor};
+
+/// The negative inner product, which is negative for similar vectors.
+struct NegativeDot;
+
+impl Metric<[f64; 4]> for NegativeDot {
+    type Unit = OrderedF64;
+
+    fn distance(&self, a: &[f64; 4], b: &[f64; 4]) -> OrderedF64 {
+        OrderedF64::new(-a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>())
+    }
+}
+
+#[test]
+fn ordering() {
+    let mut values: Vec<OrderedF32> = [3.0, -1.0, 0.5, -7.25, f32::INFINITY, -0.0]
+        .iter()
+        .map(|&x| OrderedF32::new(x))
+        .collect();
+    values.sort();
+    let values: Vec<f32> = values.into_iter().map(OrderedF32::get).collect();
+    assert_eq!(values, [-7.25, -1.0, -0.0, 0.5, 3.0, f32::INFINITY]);
+    assert_eq!(OrderedF32::new(-0.0), OrderedF32::new(0.0));
+}
+
+#[test]
+fn rejects_nan() {
+    assert_eq!(OrderedF64::try_new(f64::NAN), None);
+    assert_eq!(OrderedF32::try_from(f32::NAN), Err(NanError));
+    assert_eq!(OrderedF32::try_from(2.0).map(f32::from), Ok(2.0));
+    assert!(std::panic::catch_unwind(|| OrderedF64::new(f64::NAN)).is_err());
+    assert!(std::panic::catch_unwind(|| {
+        OrderedF64::new(f64::INFINITY) - OrderedF64::new(f64::INFINITY)
+    })
+    .is_err());
+}
+
+#[test]
+fn negative_distances() {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let features: Vec<[f64; 4]> = (0..256)
+        .map(|_| {
+            let mut feature = [0.0; 4];
+            prng.fill(&mut feature[..]);
+            feature.iter_mut().for_each(|x| *x -= 0.5);
+            feature
+        })
+        .collect();
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<NegativeDot, [f64; 4], Pcg64, 12, 24> = Hnsw::new(NegativeDot);
+    for &feature in &features {
+        hnsw.insert(feature, &mut searcher);
+    }
+
+    let query = [1.0, -0.5, 0.25, 0.0];
+    let mut expected: Vec<OrderedF64> = features
+        .iter()
+        .map(|feature| NegativeDot.distance(&query, feature))
+        .collect();
+    expected.sort();
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: OrderedF64::new(0.0),
+    }; 8];
+    let found = hnsw.nearest(&query, 256, &mut searcher, &mut neighbors);
+    assert!(found[0].distance.get() < 0.0);
+    let found: Vec<OrderedF64> = found.iter().map(|n| n.distance).collect();
+    assert_eq!(found, expected[..8]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_rejects_nan() {
+    use serde::{de::value::F64Deserializer, Deserialize};
+
+    let x = OrderedF64::new(-1.5);
+    let json = serde_json::to_string(&x).unwrap();
+    assert_eq!(json, "-1.5");
+    assert_eq!(serde_json::from_str::<OrderedF64>(&json).unwrap(), x);
+    // JSON can't hold NaN, so deserialize a NaN directly.
+    let nan = F64Deserializer::<serde::de::value::Error>::new(f64::NAN);
+    assert!(OrderedF64::deserialize(nan).is_err());
+}
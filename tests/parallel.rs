@@ -0,0 +1,259 @@
+#![cfg(feature = "rayon")]
+
+use bitarray::{BitArray, Hamming};
+use hnsw::*;
+use rand::distributions::Standard;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::Neighbor;
+
+// This can be adjusted lower if it is too slow.
+const SEARCH_SPACE_SIZE: usize = 1 << 12;
+
+/// The number of queries used to compare the recall of two builds. The recall of a build varies by a few
+/// percent with the order its items are linked in, so this needs enough queries for that to show.
+const SEARCH_COUNT: usize = 1000;
+
+fn random_features(seed: u8, count: usize) -> Vec<BitArray<16>> {
+    Pcg64::from_seed([seed; 32])
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(count)
+        .collect()
+}
+
+/// Counts how many times the HNSW finds a nearest neighbor as close as the one found with linear search.
+fn count_1_nn(
+    hnsw: &Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24>,
+    space: &[BitArray<16>],
+    search: &[BitArray<16>],
+) -> usize {
+    let mut searcher = Searcher::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+    search
+        .iter()
+        .filter(|&feature| {
+            let nearest = space
+                .iter()
+                .map(|space_feature| feature.distance(space_feature))
+                .min()
+                .unwrap();
+            let found = hnsw.nearest(feature, 24, &mut searcher, &mut output);
+            nearest == found[0].distance
+        })
+        .count()
+}
+
+/// Inserts the features one at a time, to compare a parallel build against.
+fn build_sequential(
+    params: Params,
+    space: &[BitArray<16>],
+) -> Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> {
+    let mut hnsw = Hnsw::new_params(Hamming, params);
+    let mut searcher = Searcher::default();
+    for &feature in space {
+        hnsw.insert(feature, &mut searcher);
+    }
+    hnsw
+}
+
+#[test]
+fn linear_1_nn_parallel() {
+    let space = random_features(5, SEARCH_SPACE_SIZE);
+    let search = random_features(6, SEARCH_COUNT);
+
+    let hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+        Hnsw::from_batch_parallel(Hamming, Params::new(), space.iter().copied());
+    assert_eq!(hnsw.len(), SEARCH_SPACE_SIZE);
+    for (item, feature) in space.iter().enumerate() {
+        assert_eq!(hnsw.feature(item), feature);
+    }
+
+    // It should do about as well as inserting them one at a time.
+    let mut sequential: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::new(Hamming);
+    let mut searcher = Searcher::default();
+    for &feature in &space {
+        sequential.insert(feature, &mut searcher);
+    }
+    assert_eq!(hnsw.layers(), sequential.layers());
+    hnsw.validate().unwrap();
+
+    let pass = count_1_nn(&hnsw, &space, &search);
+    let sequential_pass = count_1_nn(&sequential, &space, &search);
+    eprintln!(
+        "pass: {}/{}, sequential pass: {}/{}",
+        pass, SEARCH_COUNT, sequential_pass, SEARCH_COUNT
+    );
+    assert!(pass + SEARCH_COUNT / 20 >= sequential_pass);
+}
+
+#[test]
+fn insert_batch_parallel() {
+    let space = random_features(5, SEARCH_SPACE_SIZE);
+    let search = random_features(6, SEARCH_COUNT);
+
+    let params =
+        Params::new()
+            .inbound_edges(true)
+            .neighbor_selection(NeighborSelection::Heuristic {
+                extend_candidates: true,
+                keep_pruned_connections: false,
+            });
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::new_params(Hamming, params);
+    let mut searcher = Searcher::default();
+    let (first, rest) = space.split_at(SEARCH_SPACE_SIZE / 4);
+    for &feature in first {
+        hnsw.insert(feature, &mut searcher);
+    }
+    let items = hnsw.insert_batch_parallel(rest.iter().copied());
+    assert_eq!(items, first.len()..SEARCH_SPACE_SIZE);
+    for (item, feature) in space.iter().enumerate() {
+        assert_eq!(hnsw.feature(item), feature);
+    }
+
+    // The inbound edges are rebuilt afterwards.
+    hnsw.validate().unwrap();
+    for level in 0..hnsw.layers() {
+        for node in 0..hnsw.layer_len(level) {
+            for neighbor in hnsw.neighbors(level, node) {
+                assert!(hnsw.inbound(level, neighbor).unwrap().contains(&node));
+            }
+        }
+    }
+
+    // It should do about as well as inserting them one at a time.
+    let pass = count_1_nn(&hnsw, &space, &search);
+    let sequential_pass = count_1_nn(&build_sequential(params, &space), &space, &search);
+    eprintln!(
+        "pass: {}/{}, sequential pass: {}/{}",
+        pass, SEARCH_COUNT, sequential_pass, SEARCH_COUNT
+    );
+    assert!(pass + SEARCH_COUNT / 20 >= sequential_pass);
+}
+
+/// Builds with many threads, so that items are often linked to nodes which other threads haven't linked yet.
+#[test]
+fn insert_batch_parallel_threads() {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(16)
+        .build()
+        .unwrap();
+    let params = Params::new().ef_construction(100);
+
+    let space = random_features(5, 20_000);
+    let search = random_features(6, SEARCH_COUNT);
+    let hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+        pool.install(|| Hnsw::from_batch_parallel(Hamming, params, space.iter().copied()));
+    assert_eq!(hnsw.len(), space.len());
+    hnsw.validate().unwrap();
+    // A search can only reach a node once it is linked on every layer below, so no item is left with
+    // the few neighbors found by searching from a node that has none yet.
+    let fewest = (0..hnsw.len())
+        .map(|item| hnsw.neighbors(0, item).count())
+        .min()
+        .unwrap();
+    assert!(fewest >= 12, "an item only has {} neighbors", fewest);
+
+    // It should do about as well as inserting them one at a time.
+    let pass = count_1_nn(&hnsw, &space, &search);
+    let sequential_pass = count_1_nn(&build_sequential(params, &space), &space, &search);
+    eprintln!(
+        "pass: {}/{}, sequential pass: {}/{}",
+        pass, SEARCH_COUNT, sequential_pass, SEARCH_COUNT
+    );
+    assert!(pass + SEARCH_COUNT / 20 >= sequential_pass);
+
+    // With few neighbors, most nodes are full and have their neighbors replaced while they are linked.
+    let space: Vec<BitArray<4>> = Pcg64::from_seed([7; 32])
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(20_000)
+        .collect();
+    let hnsw: Hnsw<Hamming, BitArray<4>, Pcg64, 2, 4> =
+        pool.install(|| Hnsw::from_batch_parallel(Hamming, params, space.iter().copied()));
+    assert_eq!(hnsw.len(), space.len());
+    hnsw.validate().unwrap();
+}
+
+/// Serializes the whole HNSW, including its features, tombstones, params and PRNG, to compare two builds.
+#[cfg(feature = "serde")]
+fn serialize(hnsw: &Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24>) -> Vec<u8> {
+    hnsw.validate().unwrap();
+    bincode::serialize(hnsw).unwrap()
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn linear_1_nn_deterministic() {
+    let space = random_features(5, SEARCH_SPACE_SIZE);
+    let search = random_features(6, SEARCH_COUNT);
+
+    let build = |threads| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+                Hnsw::from_batch_deterministic(Hamming, Params::new(), space.iter().copied());
+            hnsw
+        })
+    };
+    let hnsw = build(4);
+    assert_eq!(hnsw.len(), SEARCH_SPACE_SIZE);
+    for (item, feature) in space.iter().enumerate() {
+        assert_eq!(hnsw.feature(item), feature);
+    }
+    // The serialized HNSW is the same no matter how many threads build it.
+    assert_eq!(serialize(&hnsw), serialize(&build(1)));
+    assert_eq!(serialize(&hnsw), serialize(&build(3)));
+
+    // It should do about as well as inserting them one at a time.
+    let pass = count_1_nn(&hnsw, &space, &search);
+    let sequential_pass = count_1_nn(&build_sequential(Params::new(), &space), &space, &search);
+    eprintln!(
+        "pass: {}/{}, sequential pass: {}/{}",
+        pass, SEARCH_COUNT, sequential_pass, SEARCH_COUNT
+    );
+    assert!(pass + SEARCH_COUNT / 20 >= sequential_pass);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn insert_batch_deterministic() {
+    let space = random_features(5, SEARCH_SPACE_SIZE);
+
+    let build = || {
+        let params =
+            Params::new()
+                .inbound_edges(true)
+                .neighbor_selection(NeighborSelection::Heuristic {
+                    extend_candidates: true,
+                    keep_pruned_connections: false,
+                });
+        let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+            Hnsw::new_params(Hamming, params);
+        let mut searcher = Searcher::default();
+        let (first, rest) = space.split_at(SEARCH_SPACE_SIZE / 4);
+        for &feature in first {
+            hnsw.insert(feature, &mut searcher);
+        }
+        let items = hnsw.insert_batch_deterministic(rest.iter().copied());
+        assert_eq!(items, first.len()..SEARCH_SPACE_SIZE);
+        hnsw
+    };
+    let hnsw = build();
+    assert_eq!(serialize(&hnsw), serialize(&build()));
+
+    for level in 0..hnsw.layers() {
+        for node in 0..hnsw.layer_len(level) {
+            for neighbor in hnsw.neighbors(level, node) {
+                assert!(hnsw.inbound(level, neighbor).unwrap().contains(&node));
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "persist")]
+
+mod common;
+
+use common::Hamming;
+use hnsw::*;
+use rand_pcg::Pcg64;
+use space::Neighbor;
+use std::convert::TryInto;
+
+fn test_hnsw() -> Hnsw<Hamming, u64, Pcg64, 12, 24> {
+    let params = Params::new()
+        .ef_construction(100)
+        .inbound_edges(true)
+        .neighbor_selection(NeighborSelection::Heuristic {
+            extend_candidates: false,
+            keep_pruned_connections: true,
+        });
+    common::test_hnsw(params, 1000)
+}
+
+fn save(hnsw: &Hnsw<Hamming, u64, Pcg64, 12, 24>) -> Vec<u8> {
+    let mut bytes = vec![];
+    hnsw.save(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn round_trip() {
+    let mut hnsw = test_hnsw();
+    let bytes = save(&hnsw);
+    let mut loaded: Hnsw<Hamming, u64, Pcg64, 12, 24> = Hnsw::load(&bytes[..]).unwrap();
+    assert_eq!(save(&loaded), bytes);
+
+    assert_eq!(loaded.len(), hnsw.len());
+    assert_eq!(loaded.layers(), hnsw.layers());
+    assert_eq!(loaded.tombstones(), 2);
+    assert!(loaded.is_removed(7) && loaded.is_removed(100) && !loaded.is_removed(8));
+    for level in 0..hnsw.layers() {
+        for node in 0..hnsw.layer_len(level) {
+            assert_eq!(
+                loaded.neighbors(level, node).collect::<Vec<_>>(),
+                hnsw.neighbors(level, node).collect::<Vec<_>>()
+            );
+            // The inbound edges aren't saved, but they are rebuilt.
+            let mut inbound = hnsw.inbound(level, node).unwrap().to_vec();
+            let mut loaded_inbound = loaded.inbound(level, node).unwrap().to_vec();
+            inbound.sort_unstable();
+            loaded_inbound.sort_unstable();
+            assert_eq!(loaded_inbound, inbound);
+        }
+    }
+
+    let mut searcher = Searcher::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+    let mut loaded_output = output;
+    for query in [0, !0, 0x5555_5555_5555_5555] {
+        let found = hnsw.nearest(&query, 24, &mut searcher, &mut output);
+        let loaded_found = loaded.nearest(&query, 24, &mut searcher, &mut loaded_output);
+        assert_eq!(found, loaded_found);
+    }
+
+    // The PRNG state is saved, so inserting afterwards does the same thing.
+    assert_eq!(hnsw.insert(42, &mut searcher), 1000);
+    assert_eq!(loaded.insert(42, &mut searcher), 1000);
+    assert_eq!(save(&loaded), save(&hnsw));
+}
+
+#[test]
+fn mismatched_m() {
+    let bytes = save(&test_hnsw());
+    match Hnsw::<Hamming, u64, Pcg64, 16, 24>::load(&bytes[..]) {
+        Err(PersistError::MismatchedM { expected, found }) => {
+            assert_eq!((expected, found), (16, 12))
+        }
+        _ => panic!("expected a mismatched M"),
+    }
+    match Hnsw::<Hamming, u64, Pcg64, 12, 32>::load(&bytes[..]) {
+        Err(PersistError::MismatchedM0 { expected, found }) => {
+            assert_eq!((expected, found), (32, 24))
+        }
+        _ => panic!("expected a mismatched M0"),
+    }
+}
+
+#[test]
+fn bad_header() {
+    let mut bytes = save(&test_hnsw());
+    bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Hnsw::<Hamming, u64, Pcg64, 12, 24>::load(&bytes[..]),
+        Err(PersistError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+    ));
+
+    bytes[0] = b'X';
+    assert!(matches!(
+        Hnsw::<Hamming, u64, Pcg64, 12, 24>::load(&bytes[..]),
+        Err(PersistError::BadMagic)
+    ));
+
+    let bytes = save(&test_hnsw());
+    assert!(matches!(
+        Hnsw::<Hamming, u64, Pcg64, 12, 24>::load(&bytes[..bytes.len() - 1]),
+        Err(PersistError::Io(_)) | Err(PersistError::Encoding(_))
+    ));
+}
+
+#[test]
+fn invalid_graph() {
+    let hnsw = test_hnsw();
+    let mut bytes = save(&hnsw);
+    // Skip the header to get to the first neighbor of the first item.
+    let zero = MAGIC.len() + 4 + 8 * 4 + 8 * (hnsw.layers() - 1) + 8 + 8 + 4;
+    assert_eq!(
+        u64::from_le_bytes(bytes[zero..zero + 8].try_into().unwrap()),
+        hnsw.neighbors(0, 0).next().unwrap() as u64
+    );
+    bytes[zero..zero + 8].copy_from_slice(&5000u64.to_le_bytes());
+    assert!(matches!(
+        Hnsw::<Hamming, u64, Pcg64, 12, 24>::load(&bytes[..]),
+        Err(PersistError::Invalid(ValidationError::NeighborOutOfRange {
+            level: 0,
+            node: 0,
+            neighbor: 5000
+        }))
+    ));
+}
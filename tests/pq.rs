@@ -0,0 +1,125 @@
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Metric, Neighbor};
+
+const DIM: usize = 16;
+
+type PqHnsw = Hnsw<PqCodec, PqFeature, Pcg64, 12, 24>;
+
+fn vectors(prng: &mut Pcg64, count: usize) -> Vec<Vec<f32>> {
+    (0..count)
+        .map(|_| (0..DIM).map(|_| prng.gen_range(-1.0..1.0)).collect())
+        .collect()
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(&a, &b)| (a - b) * (a - b)).sum()
+}
+
+/// Gives the codec, the vectors to insert and the vectors to search for.
+fn setup() -> (PqCodec, Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let mut data = vectors(&mut prng, 2048 + 100);
+    let codec = PqCodec::train(&data[..1024], 8, 256, 16, &mut prng);
+    let queries = data.split_off(2048);
+    (codec, data, queries)
+}
+
+#[test]
+fn asymmetric_distance() {
+    let (codec, data, _) = setup();
+    assert_eq!(codec.dim(), DIM);
+    assert_eq!(codec.subspaces(), 8);
+    assert_eq!(codec.centroids(), 256);
+
+    let encoded = codec.encode(&data[0]);
+    let codes = encoded.codes().unwrap();
+    assert_eq!(codes.len(), 8);
+    let decoded = codec.decode(codes);
+    assert!(squared_distance(&decoded, &data[0]) < squared_distance(&data[0], &data[1]));
+
+    // The query distance is the exact distance to the decoded vector.
+    let query = codec.query(&data[1]);
+    assert!(query.codes().is_none());
+    let approximate = codec.distance(&query, &encoded).get();
+    let exact = squared_distance(&decoded, &data[1]);
+    assert!((approximate - exact).abs() <= exact * 1e-4);
+    assert_eq!(
+        codec.distance(&query, &encoded),
+        codec.distance(&encoded, &query)
+    );
+
+    // Two queries are compared exactly.
+    assert_eq!(
+        codec.distance(&query, &codec.query(&data[0])).get(),
+        squared_distance(&data[1], &data[0])
+    );
+}
+
+/// Gets the message that training panics with.
+fn train_panic(sample: Vec<Vec<f32>>, subspaces: usize) -> String {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let error =
+        std::panic::catch_unwind(move || PqCodec::train(&sample, subspaces, 2, 4, &mut prng))
+            .unwrap_err();
+    error.downcast_ref::<&str>().unwrap().to_string()
+}
+
+#[test]
+fn rejects_bad_samples() {
+    assert_eq!(
+        train_panic(vec![vec![]; 4], 1),
+        "the sample vectors must not be empty"
+    );
+    let mut prng = Pcg64::from_seed([5; 32]);
+    assert_eq!(
+        train_panic(vectors(&mut prng, 4), 3),
+        "the dimension must be divisible by the number of subspaces"
+    );
+}
+
+#[test]
+fn search_and_rerank() {
+    let (codec, data, queries) = setup();
+    let mut searcher = Searcher::default();
+    let mut hnsw = PqHnsw::new(codec);
+    for v in &data {
+        let encoded = hnsw.metric().encode(v);
+        hnsw.insert(encoded, &mut searcher);
+    }
+    hnsw.validate().unwrap();
+
+    let mut found = 0;
+    for q in &queries {
+        let true_nearest = (0..data.len())
+            .min_by(|&a, &b| {
+                squared_distance(q, &data[a])
+                    .partial_cmp(&squared_distance(q, &data[b]))
+                    .unwrap()
+            })
+            .unwrap();
+
+        let query = hnsw.metric().query(q);
+        let mut neighbors = [Neighbor {
+            index: !0,
+            distance: OrderedF32::new(f32::INFINITY),
+        }; 32];
+        let neighbors = hnsw.nearest(&query, 64, &mut searcher, &mut neighbors);
+        rerank(q, neighbors, |item| &data[item]);
+        assert!(neighbors.windows(2).all(|w| w[0].distance <= w[1].distance));
+        assert_eq!(
+            neighbors[0].distance,
+            OrderedF32::new(squared_distance(q, &data[neighbors[0].index]))
+        );
+        if neighbors[0].index == true_nearest {
+            found += 1;
+        }
+    }
+    assert!(
+        found >= 90,
+        "only {} of 100 queries found their nearest",
+        found
+    );
+}
@@ -122,3 +122,589 @@ fn linear_1_nn_inliers() {
     eprintln!("pass: {}/100", pass);
     assert!(pass >= 90);
 }
+
+/// Ensures that inserting features leaves a graph which passes every check.
+#[test]
+fn validate_inserted() {
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    hnsw.validate().unwrap();
+
+    let prng = Pcg64::from_seed([5; 32]);
+    for feature in prng
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(SEARCH_SPACE_SIZE)
+    {
+        hnsw.insert(feature, &mut searcher);
+    }
+    hnsw.validate().unwrap();
+}
+
+/// Searches every layer and ensures that the item of each node found has the feature that was found.
+#[test]
+fn layer_item_id() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+    assert!(hnsw.layers() > 2);
+
+    for level in 0..hnsw.layers() {
+        for &feature in &search {
+            let found = hnsw.search_layer(&feature, 24, level, searcher, &mut output);
+            let item = hnsw.layer_item_id(level, found[0].index);
+            assert_eq!(hnsw.feature(item).distance(&feature), found[0].distance);
+            assert_eq!(
+                hnsw.layer_feature(level, found[0].index),
+                hnsw.feature(item)
+            );
+        }
+        // Every node on a layer is for a different item.
+        let mut items = (0..hnsw.layer_len(level))
+            .map(|node| hnsw.layer_item_id(level, node))
+            .collect::<Vec<_>>();
+        items.sort_unstable();
+        items.dedup();
+        assert_eq!(items.len(), hnsw.layer_len(level));
+        assert!(items.iter().all(|&item| item < hnsw.len()));
+    }
+}
+
+/// Removes half of the search space and ensures that removed features are never returned.
+#[test]
+fn linear_1_nn_removed() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    for ix in (0..SEARCH_SPACE_SIZE).step_by(2) {
+        hnsw.remove(ix);
+    }
+    assert_eq!(hnsw.tombstones(), SEARCH_SPACE_SIZE / 2);
+
+    let mut pass = 0;
+
+    for &feature in &search {
+        // Use linear search to find the nearest neighbor that was not removed.
+        let nearest = space
+            .iter()
+            .enumerate()
+            .skip(1)
+            .step_by(2)
+            .min_by_key(|(_, &space_feature)| feature.distance(&space_feature))
+            .unwrap();
+        // Use HNSW to find the nearest neighbor.
+        let found = hnsw.nearest(&feature, 24, searcher, &mut output);
+        assert_eq!(found.len(), 1);
+        assert!(!hnsw.is_removed(found[0].index));
+        if nearest.1.distance(&feature) == space[found[0].index].distance(&feature) {
+            pass += 1;
+        }
+    }
+
+    eprintln!("pass: {}/100", pass);
+    assert!(pass >= 90);
+}
+
+/// Removes half of the search space, compacts the HNSW, and ensures the rest can still be found.
+#[test]
+fn linear_1_nn_compacted() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    for ix in (0..SEARCH_SPACE_SIZE).step_by(2) {
+        hnsw.remove(ix);
+    }
+    let mapping = hnsw.compact();
+    hnsw.validate().unwrap();
+    assert_eq!(hnsw.len(), SEARCH_SPACE_SIZE / 2);
+    assert_eq!(hnsw.tombstones(), 0);
+
+    let mut pass = 0;
+
+    // Every remaining feature should be able to find itself.
+    for (old_ix, &feature) in space.iter().enumerate().skip(1).step_by(2) {
+        assert_eq!(*hnsw.feature(mapping[old_ix]), feature);
+        let found = hnsw.nearest(&feature, 24, searcher, &mut output);
+        if found[0].distance == 0 {
+            pass += 1;
+        }
+    }
+
+    eprintln!("pass: {}/{}", pass, SEARCH_SPACE_SIZE / 2);
+    assert!(pass >= SEARCH_SPACE_SIZE / 2 * 9 / 10);
+}
+
+/// Checks that the inbound edges are exactly the reverse of the neighbors on every layer.
+fn check_inbound(hnsw: &Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24>) {
+    hnsw.validate().unwrap();
+    for level in 0..hnsw.layers() {
+        let mut total_inbound = 0;
+        let mut total_outbound = 0;
+        for node in 0..hnsw.layer_len(level) {
+            total_inbound += hnsw.inbound(level, node).unwrap().len();
+            for neighbor in hnsw.neighbors(level, node) {
+                total_outbound += 1;
+                assert!(hnsw.inbound(level, neighbor).unwrap().contains(&node));
+            }
+        }
+        assert_eq!(total_inbound, total_outbound);
+    }
+}
+
+#[test]
+fn inbound_edges() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+        Hnsw::new_params(Hamming, Params::new().inbound_edges(true));
+    let mut hnsw_plain: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+    let mut output_plain = output;
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+        hnsw_plain.insert(feature, searcher);
+    }
+    assert!(hnsw_plain.inbound(0, 0).is_none());
+    check_inbound(&hnsw);
+
+    for ix in (0..SEARCH_SPACE_SIZE).step_by(3) {
+        hnsw.remove(ix);
+        hnsw_plain.remove(ix);
+    }
+    assert_eq!(hnsw.compact(), hnsw_plain.compact());
+    check_inbound(&hnsw);
+
+    // Tracking inbound edges must not change the graph.
+    for &feature in &search {
+        let found = hnsw.nearest(&feature, 24, searcher, &mut output);
+        let found_plain = hnsw_plain.nearest(&feature, 24, searcher, &mut output_plain);
+        assert_eq!(found, found_plain);
+    }
+}
+
+/// Replaces half of the features with new random features and ensures all features can still be found.
+#[test]
+fn linear_1_nn_updated() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+        Hnsw::new_params(Hamming, Params::new().inbound_edges(true));
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let mut space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    for ix in (0..SEARCH_SPACE_SIZE).step_by(2) {
+        space[ix] = rngiter.next().unwrap();
+        hnsw.update(ix, space[ix], searcher);
+    }
+    check_inbound(&hnsw);
+
+    let mut pass = 0;
+
+    // Every feature should be able to find itself.
+    for (ix, feature) in space.iter().enumerate() {
+        assert_eq!(hnsw.feature(ix), feature);
+        let found = hnsw.nearest(feature, 24, searcher, &mut output);
+        if found[0].distance == 0 {
+            pass += 1;
+        }
+    }
+
+    eprintln!("pass: {}/{}", pass, SEARCH_SPACE_SIZE);
+    assert!(pass >= SEARCH_SPACE_SIZE * 9 / 10);
+}
+
+/// Ensures that updating features relinks the graph the same way with and without inbound edges.
+#[test]
+fn update_without_inbound_edges() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+        Hnsw::new_params(Hamming, Params::new().inbound_edges(true));
+    let mut hnsw_plain: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+        hnsw_plain.insert(feature, searcher);
+    }
+
+    for ix in (0..SEARCH_SPACE_SIZE).step_by(3) {
+        let feature = rngiter.next().unwrap();
+        hnsw.update(ix, feature, searcher);
+        hnsw_plain.update(ix, feature, searcher);
+    }
+    check_inbound(&hnsw);
+
+    // Every node that pointed to an updated item must be found even without inbound edges.
+    for level in 0..hnsw.layers() {
+        for node in 0..hnsw.layer_len(level) {
+            assert!(hnsw
+                .neighbors(level, node)
+                .eq(hnsw_plain.neighbors(level, node)));
+        }
+    }
+}
+
+/// Searches with a selective filter and ensures the nearest accepted feature is found.
+#[test]
+fn linear_1_nn_filtered() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    let filter = |ix: usize| ix % 32 == 3;
+    let mut pass = 0;
+
+    for &feature in &search {
+        // Use linear search to find the nearest neighbor that passes the filter.
+        let nearest = space
+            .iter()
+            .enumerate()
+            .filter(|&(ix, _)| filter(ix))
+            .min_by_key(|(_, &space_feature)| feature.distance(&space_feature))
+            .unwrap();
+        // Use HNSW to find the nearest neighbor.
+        let found = hnsw.nearest_filtered(&feature, 24, filter, searcher, &mut output);
+        assert_eq!(found.len(), 1);
+        assert!(filter(found[0].index));
+        if nearest.1.distance(&feature) == found[0].distance {
+            pass += 1;
+        }
+    }
+
+    eprintln!("pass: {}/100", pass);
+    assert!(pass >= 90);
+}
+
+/// Finds all features within a radius and compares them to a linear search.
+#[test]
+fn linear_within() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    const RADIUS: u32 = 60;
+    let mut expected = 0;
+    let mut found = 0;
+
+    for &feature in &search {
+        // Use linear search to find all of the features within the radius.
+        expected += space
+            .iter()
+            .filter(|space_feature| feature.distance(space_feature) <= RADIUS)
+            .count();
+        // Use HNSW to find all of the features within the radius.
+        let neighbors = hnsw.within(&feature, RADIUS, searcher);
+        for neighbor in neighbors {
+            assert!(neighbor.distance <= RADIUS);
+            assert_eq!(neighbor.distance, space[neighbor.index].distance(&feature));
+        }
+        found += neighbors.len();
+    }
+
+    eprintln!("found: {}/{}", found, expected);
+    assert!(found >= expected * 9 / 10);
+}
+
+/// Generates a clustered search space and ensures the heuristic neighbor selection can search it.
+#[test]
+fn linear_1_nn_heuristic_clustered() {
+    const BIT_DIFF_PROBABILITY_OF_CLUSTER: f64 = 0.05;
+
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let centers = (&mut prng)
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(16)
+        .collect::<Vec<BitArray<16>>>();
+    let bernoulli = Bernoulli::new(BIT_DIFF_PROBABILITY_OF_CLUSTER).unwrap();
+    let clustered = |prng: &mut Pcg64| {
+        let mut feature = *centers.choose(prng).unwrap();
+        for bit in 0..128 {
+            let choice: bool = prng.sample(bernoulli);
+            feature[bit / 8] ^= (choice as u8) << (bit % 8);
+        }
+        feature
+    };
+    let space = (0..SEARCH_SPACE_SIZE)
+        .map(|_| clustered(&mut prng))
+        .collect::<Vec<_>>();
+    let search = (0..100).map(|_| clustered(&mut prng)).collect::<Vec<_>>();
+
+    for &extend_candidates in &[false, true] {
+        for &keep_pruned_connections in &[false, true] {
+            let mut searcher = Searcher::default();
+            let searcher = &mut searcher;
+            let params = Params::new().neighbor_selection(NeighborSelection::Heuristic {
+                extend_candidates,
+                keep_pruned_connections,
+            });
+            let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> =
+                Hnsw::new_params(Hamming, params);
+            let mut output = [Neighbor {
+                index: !0,
+                distance: !0,
+            }; 1];
+
+            for &feature in &space {
+                hnsw.insert(feature, searcher);
+            }
+
+            let mut pass = 0;
+
+            for &feature in &search {
+                // Use linear search to find the nearest neighbor.
+                let nearest = space
+                    .iter()
+                    .map(|space_feature| feature.distance(space_feature))
+                    .min()
+                    .unwrap();
+                // Use HNSW to find the nearest neighbor.
+                let found = hnsw.nearest(&feature, 24, searcher, &mut output);
+                if nearest == found[0].distance {
+                    pass += 1;
+                }
+            }
+
+            eprintln!(
+                "extend_candidates: {}, keep_pruned_connections: {}, pass: {}/100",
+                extend_candidates, keep_pruned_connections, pass
+            );
+            assert!(pass >= 90);
+        }
+    }
+}
+
+/// Only keeps half as many neighbors as there are slots.
+struct SelectHalf;
+
+impl NeighborSelector<Hamming, BitArray<16>> for SelectHalf {
+    fn select_neighbors<G: LayerView<BitArray<16>>>(
+        &self,
+        metric: &Hamming,
+        graph: &G,
+        node: usize,
+        candidates: &[Neighbor<u32>],
+        max_neighbors: usize,
+    ) -> Vec<Neighbor<u32>> {
+        SelectClosest.select_neighbors(metric, graph, node, candidates, max_neighbors / 2)
+    }
+}
+
+#[test]
+fn linear_1_nn_custom_selector() {
+    let mut searcher = Searcher::default();
+    let searcher = &mut searcher;
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24, SelectHalf> =
+        Hnsw::new(Hamming).with_selector(SelectHalf);
+    let mut output = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 1];
+
+    let prng = Pcg64::from_seed([5; 32]);
+    let mut rngiter = prng.sample_iter(&Standard).map(BitArray::new);
+    let space = (&mut rngiter).take(SEARCH_SPACE_SIZE).collect::<Vec<_>>();
+    let search = (&mut rngiter).take(100).collect::<Vec<_>>();
+
+    for &feature in &space {
+        hnsw.insert(feature, searcher);
+    }
+
+    // The selector is consulted for every edge, so no node has more neighbors than it keeps.
+    for level in 0..hnsw.layers() {
+        let max_neighbors = if level == 0 { 24 } else { 12 } / 2;
+        for node in 0..hnsw.layer_len(level) {
+            assert!(hnsw.neighbors(level, node).count() <= max_neighbors);
+        }
+    }
+
+    let mut pass = 0;
+
+    for &feature in &search {
+        // Use linear search to find the nearest neighbor.
+        let nearest = space
+            .iter()
+            .map(|space_feature| feature.distance(space_feature))
+            .min()
+            .unwrap();
+        // Use HNSW to find the nearest neighbor.
+        let found = hnsw.nearest(&feature, 24, searcher, &mut output);
+        if nearest == found[0].distance {
+            pass += 1;
+        }
+    }
+
+    eprintln!("pass: {}/100", pass);
+    assert!(pass >= 10);
+}
+
+#[test]
+fn graph_stats() {
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<Hamming, BitArray<16>, Pcg64, 12, 24> = Hnsw::default();
+
+    let stats = hnsw.stats();
+    assert_eq!(stats.layers.len(), 1);
+    assert_eq!(stats.layers[0].nodes, 0);
+    assert_eq!(stats.layers[0].out_degrees, vec![0; 25]);
+    assert_eq!(stats.layers[0].mean_edge_distance, None);
+
+    let prng = Pcg64::from_seed([5; 32]);
+    for feature in prng
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(SEARCH_SPACE_SIZE)
+    {
+        hnsw.insert(feature, &mut searcher);
+    }
+
+    let stats = hnsw.stats();
+    assert_eq!(stats.layers.len(), hnsw.layers());
+    for (level, layer) in stats.layers.iter().enumerate() {
+        assert_eq!(layer.nodes, hnsw.layer_len(level));
+        assert_eq!(layer.out_degrees.len(), if level == 0 { 25 } else { 13 });
+        assert_eq!(layer.out_degrees.iter().sum::<usize>(), layer.nodes);
+        assert_eq!(layer.in_degrees.iter().sum::<usize>(), layer.nodes);
+        let weighted = |degrees: &[usize]| -> usize {
+            degrees
+                .iter()
+                .enumerate()
+                .map(|(degree, count)| degree * count)
+                .sum()
+        };
+        assert_eq!(weighted(&layer.out_degrees), layer.edges);
+        assert_eq!(weighted(&layer.in_degrees), layer.edges);
+        assert!(layer.unreachable < layer.nodes);
+        if layer.edges != 0 {
+            let mean = layer.mean_edge_distance.unwrap();
+            assert!(mean > 0.0 && mean < 128.0);
+        }
+    }
+    // Inserting every item links it to its neighbors, so almost every item is reachable.
+    assert!(stats.layers[0].unreachable < SEARCH_SPACE_SIZE / 100);
+    assert_eq!(stats.layers[0].out_degrees[0], 0);
+}
+
+/// Many equal features make the neighbors of most nodes full of closer nodes, which leaves many unreachable.
+#[test]
+fn repair_unreachable() {
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<Hamming, BitArray<1>, Pcg64, 4, 8> = Hnsw::new_params(
+        Hamming,
+        Params::new().ef_construction(20).inbound_edges(true),
+    );
+    let prng = Pcg64::from_seed([5; 32]);
+    for feature in prng
+        .sample_iter(&Standard)
+        .map(BitArray::new)
+        .take(SEARCH_SPACE_SIZE * 2)
+    {
+        hnsw.insert(feature, &mut searcher);
+    }
+    for item in (0..SEARCH_SPACE_SIZE * 2).step_by(7) {
+        hnsw.remove(item);
+    }
+
+    let unreachable = hnsw.find_unreachable();
+    assert_eq!(unreachable.len(), hnsw.layers());
+    assert!(unreachable[0].len() > SEARCH_SPACE_SIZE / 10);
+    let stats = hnsw.stats();
+    for (layer, nodes) in stats.layers.iter().zip(&unreachable) {
+        assert_eq!(layer.unreachable, nodes.len());
+    }
+
+    let linked = hnsw.repair(&mut searcher);
+    assert!(linked > 0);
+    hnsw.validate().unwrap();
+    for (level, nodes) in hnsw.find_unreachable().iter().enumerate() {
+        for &node in nodes {
+            assert!(hnsw.is_removed(hnsw.layer_item_id(level, node)));
+        }
+    }
+    assert_eq!(hnsw.repair(&mut searcher), 0);
+}
@@ -1,8 +1,9 @@
 #![cfg(feature = "serde")]
 
 use hnsw::{Hnsw, Searcher};
-use rand_pcg::Pcg64;
+use rand_pcg::{Pcg32, Pcg64};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use space::{Metric, Neighbor};
 
 #[derive(Serialize, Deserialize)]
@@ -87,3 +88,44 @@ fn serde() {
         ]
     );
 }
+
+/// Deserializes a HNSW after breaking it with `corrupt`, returning the error message.
+fn deserialize_corrupted(corrupt: impl FnOnce(&mut Value)) -> String {
+    // `Pcg64` can't be converted to a `Value`, since its state doesn't fit in a JSON number.
+    let mut searcher = Searcher::default();
+    let mut hnsw: Hnsw<Hamming, u8, Pcg32, 12, 24> = Hnsw::new(Hamming);
+    for &feature in &[
+        0b0001, 0b0010, 0b0100, 0b1000, 0b0011, 0b0110, 0b1100, 0b1001,
+    ] {
+        hnsw.insert(feature, &mut searcher);
+    }
+    hnsw.validate().unwrap();
+    let mut value = serde_json::to_value(&hnsw).expect("failed to serialize hnsw");
+    corrupt(&mut value);
+    match serde_json::from_value::<Hnsw<Hamming, u8, Pcg32, 12, 24>>(value) {
+        Ok(_) => panic!("deserialized a corrupted hnsw"),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn serde_validates() {
+    assert_eq!(
+        deserialize_corrupted(|value| value["zero"][2][0] = 8.into()),
+        "node 2 on level 0 has neighbor 8, which does not exist"
+    );
+    let duplicate =
+        deserialize_corrupted(|value| value["zero"][2][1] = value["zero"][2][0].clone());
+    assert!(duplicate.starts_with("node 2 on level 0 has neighbor"));
+    assert!(duplicate.ends_with("more than once"));
+    assert_eq!(
+        deserialize_corrupted(|value| {
+            value["features"].as_array_mut().unwrap().pop();
+        }),
+        "there are 7 features for 8 items"
+    );
+    assert_eq!(
+        deserialize_corrupted(|value| value["tombstones"] = 1.into()),
+        "there are 1 tombstones, but 0 items are removed"
+    );
+}
@@ -87,3 +87,205 @@ fn nearest_neighbor_discrete() {
         ]
     );
 }
+
+#[test]
+fn remove_discrete() {
+    let (mut hnsw, mut searcher) = test_hnsw_discrete();
+    assert!(hnsw.remove(0));
+    assert!(hnsw.remove(4));
+    assert!(!hnsw.remove(4));
+    assert_eq!(hnsw.tombstones(), 2);
+    assert_eq!(hnsw.len(), 8);
+
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+
+    let found = hnsw.nearest(&0b0001, 24, &mut searcher, &mut neighbors);
+    // Distance 1
+    found[0..1].sort_unstable();
+    // Distance 2
+    found[1..4].sort_unstable();
+    // Distance 3
+    found[4..6].sort_unstable();
+    assert_eq!(
+        found,
+        [
+            Neighbor {
+                index: 7,
+                distance: 1
+            },
+            Neighbor {
+                index: 1,
+                distance: 2
+            },
+            Neighbor {
+                index: 2,
+                distance: 2
+            },
+            Neighbor {
+                index: 3,
+                distance: 2
+            },
+            Neighbor {
+                index: 5,
+                distance: 3
+            },
+            Neighbor {
+                index: 6,
+                distance: 3
+            }
+        ]
+    );
+}
+
+#[test]
+fn compact_discrete() {
+    let (mut hnsw, mut searcher) = test_hnsw_discrete();
+    hnsw.remove(0);
+    hnsw.remove(4);
+    let mapping = hnsw.compact();
+    assert_eq!(mapping, [!0, 0, 1, 2, !0, 3, 4, 5]);
+    assert_eq!(hnsw.tombstones(), 0);
+    assert_eq!(hnsw.len(), 6);
+    assert_eq!(*hnsw.feature(mapping[7]), 0b1001);
+
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+
+    let found = hnsw.nearest(&0b0001, 24, &mut searcher, &mut neighbors);
+    // Distance 1
+    found[0..1].sort_unstable();
+    // Distance 2
+    found[1..4].sort_unstable();
+    // Distance 3
+    found[4..6].sort_unstable();
+    assert_eq!(
+        found,
+        [
+            Neighbor {
+                index: 5,
+                distance: 1
+            },
+            Neighbor {
+                index: 0,
+                distance: 2
+            },
+            Neighbor {
+                index: 1,
+                distance: 2
+            },
+            Neighbor {
+                index: 2,
+                distance: 2
+            },
+            Neighbor {
+                index: 3,
+                distance: 3
+            },
+            Neighbor {
+                index: 4,
+                distance: 3
+            }
+        ]
+    );
+}
+
+#[test]
+fn update_discrete() {
+    let (mut hnsw, mut searcher) = test_hnsw_discrete();
+    hnsw.update(0, 0b1110, &mut searcher);
+    assert_eq!(*hnsw.feature(0), 0b1110);
+    assert_eq!(hnsw.len(), 8);
+
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+
+    let found = hnsw.nearest(&0b1110, 24, &mut searcher, &mut neighbors);
+    assert_eq!(found.len(), 8);
+    assert_eq!(
+        found[0],
+        Neighbor {
+            index: 0,
+            distance: 0
+        }
+    );
+    // Distance 1
+    found[1..3].sort_unstable();
+    assert_eq!(
+        found[1..3],
+        [
+            Neighbor {
+                index: 5,
+                distance: 1
+            },
+            Neighbor {
+                index: 6,
+                distance: 1
+            }
+        ]
+    );
+}
+
+#[test]
+fn nearest_filtered_discrete() {
+    let (mut hnsw, mut searcher) = test_hnsw_discrete();
+    hnsw.remove(7);
+
+    let mut neighbors = [Neighbor {
+        index: !0,
+        distance: !0,
+    }; 8];
+
+    // Only accept odd items.
+    let found = hnsw.nearest_filtered(&0b0001, 24, |ix| ix % 2 == 1, &mut searcher, &mut neighbors);
+    // Distance 2
+    found[0..2].sort_unstable();
+    assert_eq!(
+        found,
+        [
+            Neighbor {
+                index: 1,
+                distance: 2
+            },
+            Neighbor {
+                index: 3,
+                distance: 2
+            },
+            Neighbor {
+                index: 5,
+                distance: 3
+            }
+        ]
+    );
+}
+
+#[test]
+fn within_discrete() {
+    let (hnsw, mut searcher) = test_hnsw_discrete();
+    let mut found = hnsw.within(&0b0001, 1, &mut searcher).to_vec();
+    // Distance 1
+    found[1..3].sort_unstable();
+    assert_eq!(
+        found,
+        [
+            Neighbor {
+                index: 0,
+                distance: 0
+            },
+            Neighbor {
+                index: 4,
+                distance: 1
+            },
+            Neighbor {
+                index: 7,
+                distance: 1
+            }
+        ]
+    );
+}
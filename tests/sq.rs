@@ -0,0 +1,98 @@
+use hnsw::*;
+use rand::Rng;
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64;
+use space::{Metric, Neighbor};
+
+const DIM: usize = 16;
+
+type ScalarHnsw = Hnsw<ScalarCodec, ScalarFeature, Pcg64, 12, 24>;
+
+fn vectors(prng: &mut Pcg64, count: usize) -> Vec<Vec<f32>> {
+    (0..count)
+        .map(|_| {
+            (0..DIM)
+                .map(|dim| prng.gen_range(-1.0..1.0) * (dim + 1) as f32)
+                .collect()
+        })
+        .collect()
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(&a, &b)| (a - b) * (a - b)).sum()
+}
+
+/// Gives the codec, the vectors to insert and the vectors to search for.
+fn setup() -> (ScalarCodec, Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    let mut prng = Pcg64::from_seed([5; 32]);
+    let mut data = vectors(&mut prng, 2048 + 100);
+    let codec = ScalarCodec::train(&data[..1024]);
+    let queries = data.split_off(2048);
+    (codec, data, queries)
+}
+
+#[test]
+fn quantized_distance() {
+    let (codec, data, _) = setup();
+    assert_eq!(codec.dim(), DIM);
+
+    let encoded = codec.encode(&data[0]);
+    let decoded = codec.decode(encoded.codes().unwrap());
+    for (dim, (&x, &y)) in data[0].iter().zip(&decoded).enumerate() {
+        // Each code is within half a step of the value.
+        assert!((x - y).abs() <= (dim + 1) as f32 / 255.0 + 1e-4);
+    }
+
+    let query = codec.query(&data[1]);
+    assert!(query.codes().is_none());
+    let quantized = codec.distance(&query, &encoded).get();
+    let exact = squared_distance(&decoded, &data[1]);
+    assert!((quantized - exact).abs() <= exact * 1e-4);
+    // Two queries are compared exactly.
+    assert_eq!(
+        codec.distance(&query, &codec.query(&data[0])).get(),
+        squared_distance(&data[1], &data[0])
+    );
+
+    // Values outside of the trained range are clamped.
+    let outside = codec.encode(&[1000.0; DIM]);
+    assert!(outside.codes().unwrap().iter().all(|&code| code == 255));
+}
+
+#[test]
+fn search_and_rescore() {
+    let (codec, data, queries) = setup();
+    let mut searcher = Searcher::default();
+    let mut hnsw = ScalarHnsw::new(codec);
+    for v in &data {
+        let encoded = hnsw.metric().encode(v);
+        hnsw.insert(encoded, &mut searcher);
+    }
+
+    let mut found = 0;
+    for q in &queries {
+        let true_nearest = (0..data.len())
+            .min_by(|&a, &b| {
+                squared_distance(q, &data[a])
+                    .partial_cmp(&squared_distance(q, &data[b]))
+                    .unwrap()
+            })
+            .unwrap();
+
+        let query = hnsw.metric().query(q);
+        let mut neighbors = [Neighbor {
+            index: !0,
+            distance: OrderedF32::new(f32::INFINITY),
+        }; 8];
+        let neighbors = hnsw.nearest(&query, 64, &mut searcher, &mut neighbors);
+        rerank(q, neighbors, |item| &data[item]);
+        if neighbors[0].index == true_nearest {
+            found += 1;
+        }
+    }
+    assert!(
+        found >= 95,
+        "only {} of 100 queries found their nearest",
+        found
+    );
+}